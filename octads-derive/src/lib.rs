@@ -0,0 +1,140 @@
+//! Derive macros for [octads](https://docs.rs/octads), use them through its `derive`
+//! feature.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::spanned::Spanned;
+use syn::{parse_macro_input, parse_quote, Data, DeriveInput, Error, Index, Member, Type};
+
+/// Implements `Nodable` for a struct whose link to the next node is the field marked with
+/// `#[nodable(next)]`, which must be of type `*mut Self`.
+///
+/// `Default` is implemented as well, with a null link, uninit `MaybeUninit` fields and
+/// default values for the other fields. Put `#[nodable(no_default)]` on the struct to
+/// write it by hand instead.
+#[proc_macro_derive(Nodable, attributes(nodable))]
+pub fn derive_nodable(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let mut no_default = false;
+    for attr in input
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("nodable"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("no_default") {
+                no_default = true;
+                Ok(())
+            } else {
+                Err(meta.error("unsupported nodable attribute, expected `no_default`"))
+            }
+        })?;
+    }
+
+    let Data::Struct(data) = &input.data else {
+        return Err(Error::new(
+            input.span(),
+            "Nodable can only be derived for structs",
+        ));
+    };
+
+    let mut next = None;
+    for (i, field) in data.fields.iter().enumerate() {
+        for attr in field
+            .attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("nodable"))
+        {
+            attr.parse_nested_meta(|meta| {
+                if !meta.path.is_ident("next") {
+                    return Err(meta.error("unsupported nodable attribute, expected `next`"));
+                }
+                if next.is_some() {
+                    return Err(meta.error("duplicate `#[nodable(next)]` field"));
+                }
+                next = Some(match &field.ident {
+                    Some(ident) => Member::Named(ident.clone()),
+                    None => Member::Unnamed(Index::from(i)),
+                });
+                Ok(())
+            })?;
+        }
+    }
+    let Some(next) = next else {
+        return Err(Error::new(
+            input.span(),
+            "missing a `#[nodable(next)]` field linking to the next node",
+        ));
+    };
+
+    let name = &input.ident;
+    let mut generics = input.generics.clone();
+    let mut values = Vec::new();
+    for (i, field) in data.fields.iter().enumerate() {
+        let member = match &field.ident {
+            Some(ident) => Member::Named(ident.clone()),
+            None => Member::Unnamed(Index::from(i)),
+        };
+        let value = if member == next {
+            quote! { ::core::ptr::null_mut() }
+        } else if is_maybe_uninit(&field.ty) {
+            quote! { ::core::mem::MaybeUninit::uninit() }
+        } else {
+            // Bounding field types rather than type parameters keeps `MaybeUninit<T>`
+            // fields from requiring `T: Default`
+            let ty = &field.ty;
+            generics
+                .make_where_clause()
+                .predicates
+                .push(parse_quote! { #ty: ::core::default::Default });
+            quote! { ::core::default::Default::default() }
+        };
+        values.push(quote! { #member: #value });
+    }
+    if no_default {
+        generics = input.generics.clone();
+    }
+
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let nodable = quote! {
+        impl #impl_generics ::octads::allocator::Nodable for #name #ty_generics #where_clause {
+            fn next(&self) -> *mut Self {
+                self.#next
+            }
+
+            fn next_mut(&mut self) -> &mut *mut Self {
+                &mut self.#next
+            }
+        }
+    };
+    if no_default {
+        return Ok(nodable);
+    }
+    Ok(quote! {
+        #nodable
+
+        impl #impl_generics ::core::default::Default for #name #ty_generics #where_clause {
+            fn default() -> Self {
+                Self { #(#values),* }
+            }
+        }
+    })
+}
+
+fn is_maybe_uninit(ty: &Type) -> bool {
+    match ty {
+        Type::Path(path) => path
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "MaybeUninit"),
+        _ => false,
+    }
+}
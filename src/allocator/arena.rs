@@ -0,0 +1,314 @@
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use core::ptr::{self, NonNull};
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use super::{AllocError, Allocator, AllocatorStats, BlockAllocator, Global, Nodable};
+
+/// Source of arena ids, so that a handle is never accepted by another arena.
+static NEXT_ARENA_ID: AtomicUsize = AtomicUsize::new(0);
+
+struct Slot<T> {
+    value: Option<T>,
+    generation: u32,
+    /// Next vacant slot, only meaningful while `value` is `None`.
+    next: *mut Slot<T>,
+    /// Every slot ever handed out is linked through here, for dropping.
+    all: *mut Slot<T>,
+}
+
+impl<T> Default for Slot<T> {
+    fn default() -> Self {
+        Self {
+            value: None,
+            generation: 0,
+            next: ptr::null_mut(),
+            all: ptr::null_mut(),
+        }
+    }
+}
+
+impl<T> Nodable for Slot<T> {
+    fn next(&self) -> *mut Self {
+        self.next
+    }
+
+    fn next_mut(&mut self) -> &mut *mut Self {
+        &mut self.next
+    }
+}
+
+/// A reference to a value stored in a [`NodeArena`].
+///
+/// Handles are plain copyable ids: once their value is removed, they are rejected by the
+/// arena instead of aliasing whatever value reuses the slot.
+pub struct NodeHandle<T> {
+    slot: NonNull<Slot<T>>,
+    generation: u32,
+    arena: usize,
+}
+
+// Handles are never dereferenced without their arena
+unsafe impl<T> Send for NodeHandle<T> {}
+unsafe impl<T> Sync for NodeHandle<T> {}
+
+impl<T> Clone for NodeHandle<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for NodeHandle<T> {}
+
+impl<T> PartialEq for NodeHandle<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.slot == other.slot && self.generation == other.generation && self.arena == other.arena
+    }
+}
+
+impl<T> Eq for NodeHandle<T> {}
+
+impl<T> Hash for NodeHandle<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.slot.hash(state);
+        self.generation.hash(state);
+        self.arena.hash(state);
+    }
+}
+
+impl<T> fmt::Debug for NodeHandle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NodeHandle")
+            .field("slot", &self.slot)
+            .field("generation", &self.generation)
+            .field("arena", &self.arena)
+            .finish()
+    }
+}
+
+/// Safe, handle-based storage on top of a [`BlockAllocator`].
+///
+/// Values are addressed by [`NodeHandle`]s, so linked structures can store handles to
+/// each other instead of raw pointers. Each slot keeps a generation counter bumped on
+/// removal: stale handles are detected and never give access to a newer value.
+pub struct NodeArena<T, A = Global>
+where
+    A: Allocator,
+{
+    allocator: BlockAllocator<Slot<T>, A>,
+    id: usize,
+    vacant: *mut Slot<T>,
+    slots: *mut Slot<T>,
+    len: usize,
+}
+
+#[cfg(feature = "alloc")]
+impl<T> Default for NodeArena<T> {
+    fn default() -> Self {
+        Self::new(
+            BlockAllocator::<Slot<T>>::DEFAULT_BLOCK_SIZE,
+            BlockAllocator::<Slot<T>>::DEFAULT_BLOCK_CAP,
+        )
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T> NodeArena<T> {
+    pub fn new(block_size: usize, blocks_cap: usize) -> Self {
+        Self::new_in(block_size, blocks_cap, Global)
+    }
+}
+
+impl<T, A> NodeArena<T, A>
+where
+    A: Allocator,
+{
+    pub fn new_in(block_size: usize, blocks_cap: usize, alloc: A) -> Self {
+        Self {
+            allocator: BlockAllocator::new_in(block_size, blocks_cap, alloc),
+            id: NEXT_ARENA_ID.fetch_add(1, Ordering::Relaxed),
+            vacant: ptr::null_mut(),
+            slots: ptr::null_mut(),
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn insert(&mut self, value: T) -> NodeHandle<T> {
+        self.try_insert(value).unwrap_or_else(|err| err.handle())
+    }
+
+    pub fn try_insert(&mut self, value: T) -> Result<NodeHandle<T>, AllocError> {
+        let slot = if self.vacant.is_null() {
+            let slot = self.allocator.try_get_node()?;
+            unsafe { (*slot).all = self.slots };
+            self.slots = slot;
+            slot
+        } else {
+            let slot = self.vacant;
+            self.vacant = unsafe { (*slot).next };
+            slot
+        };
+        unsafe { (*slot).value = Some(value) };
+        self.len += 1;
+        Ok(NodeHandle {
+            slot: unsafe { NonNull::new_unchecked(slot) },
+            generation: unsafe { (*slot).generation },
+            arena: self.id,
+        })
+    }
+
+    /// Takes the value out, invalidating every copy of `handle`.
+    pub fn remove(&mut self, handle: NodeHandle<T>) -> Option<T> {
+        let slot = self.slot(handle)?;
+        let value = unsafe { (*slot).value.take() };
+        self.len -= 1;
+        unsafe {
+            (*slot).generation = (*slot).generation.wrapping_add(1);
+            // A slot whose generation wrapped around is retired rather than reused
+            if (*slot).generation != 0 {
+                (*slot).next = self.vacant;
+                self.vacant = slot;
+            }
+        }
+        value
+    }
+
+    pub fn contains(&self, handle: NodeHandle<T>) -> bool {
+        self.slot(handle).is_some()
+    }
+
+    pub fn get(&self, handle: NodeHandle<T>) -> Option<&T> {
+        self.slot(handle)
+            .and_then(|slot| unsafe { (*slot).value.as_ref() })
+    }
+
+    pub fn get_mut(&mut self, handle: NodeHandle<T>) -> Option<&mut T> {
+        self.slot(handle)
+            .and_then(|slot| unsafe { (*slot).value.as_mut() })
+    }
+
+    pub fn allocator_stats(&self) -> AllocatorStats {
+        self.allocator.stats()
+    }
+
+    /// The slot of `handle` if it still holds the value it was created for.
+    fn slot(&self, handle: NodeHandle<T>) -> Option<*mut Slot<T>> {
+        if handle.arena != self.id {
+            return None;
+        }
+        // Slots are never given back to the allocator, so a handle of this arena always
+        // points to a live slot
+        let slot = handle.slot.as_ptr();
+        let occupied =
+            unsafe { (*slot).generation == handle.generation && (*slot).value.is_some() };
+        occupied.then_some(slot)
+    }
+}
+
+impl<T, A> Drop for NodeArena<T, A>
+where
+    A: Allocator,
+{
+    fn drop(&mut self) {
+        let mut slot = self.slots;
+        while !slot.is_null() {
+            unsafe {
+                let next = (*slot).all;
+                ptr::drop_in_place(slot);
+                slot = next;
+            }
+        }
+    }
+}
+
+impl<T, A> fmt::Debug for NodeArena<T, A>
+where
+    A: Allocator,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NodeArena")
+            .field("id", &self.id)
+            .field("len", &self.len)
+            .finish()
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use super::*;
+
+    struct ListNode {
+        val: u32,
+        next: Option<NodeHandle<ListNode>>,
+    }
+
+    #[test]
+    fn node_arena_ok() {
+        let mut arena: NodeArena<ListNode> = NodeArena::new(2, 1);
+        let mut head = None;
+        for val in 0..5 {
+            head = Some(arena.insert(ListNode { val, next: head }));
+        }
+        assert_eq!(5, arena.len());
+
+        let mut sum = 0;
+        let mut current = head;
+        while let Some(handle) = current {
+            let node = arena.get_mut(handle).unwrap();
+            node.val *= 2;
+            sum += node.val;
+            current = node.next;
+        }
+        assert_eq!(20, sum);
+
+        let first = head.unwrap();
+        head = arena.get(first).unwrap().next;
+        assert_eq!(8, arena.remove(first).unwrap().val);
+        assert!(!arena.contains(first));
+        assert!(arena.get(first).is_none());
+        assert!(arena.remove(first).is_none());
+
+        // The slot is reused, old handles still see nothing
+        let handle = arena.insert(ListNode {
+            val: 42,
+            next: head,
+        });
+        assert!(arena.get(first).is_none());
+        assert_eq!(42, arena.get(handle).unwrap().val);
+        assert_eq!(5, arena.allocator_stats().live_nodes);
+    }
+
+    #[test]
+    fn node_arena_foreign_handle() {
+        let mut arena: NodeArena<u32> = NodeArena::new(4, 1);
+        let mut other: NodeArena<u32> = NodeArena::new(4, 1);
+        let handle = arena.insert(1);
+        other.insert(2);
+        assert!(other.get(handle).is_none());
+        assert!(other.remove(handle).is_none());
+        assert_eq!(Some(&1), arena.get(handle));
+    }
+
+    #[test]
+    fn node_arena_retired_slot() {
+        let mut arena: NodeArena<u32> = NodeArena::new(4, 1);
+        let handle = arena.insert(1);
+        unsafe { (*handle.slot.as_ptr()).generation = u32::MAX };
+        let handle = NodeHandle {
+            generation: u32::MAX,
+            ..handle
+        };
+        assert_eq!(Some(1), arena.remove(handle));
+        assert!(arena.vacant.is_null());
+        let other = arena.insert(2);
+        assert_ne!(handle.slot, other.slot);
+    }
+}
@@ -0,0 +1,435 @@
+use core::alloc::Layout;
+use core::mem::{self, MaybeUninit};
+use core::ptr::{self, NonNull};
+
+use super::{AllocError, Allocator, AllocatorStats, Global, Nodable, NodePool};
+use crate::sync::atomic::{AtomicPtr, AtomicU32, AtomicU64, AtomicUsize, Ordering};
+
+/// Block `i` holds `block_size << i` slots, so the table never has to grow.
+const MAX_BLOCKS: usize = usize::BITS as usize;
+
+/// Slots are linked by index out of the node itself, so that a stale read of a free
+/// slot's link never races with the thread that got the node.
+#[repr(C)]
+struct Slot<N> {
+    node: MaybeUninit<N>,
+    next: AtomicU32,
+    index: u32,
+}
+
+/// A thread-safe [`BlockAllocator`](super::BlockAllocator).
+///
+/// Free nodes are kept on a lock-free (Treiber) stack. Its head packs the index of the
+/// top slot (plus one, zero meaning empty) with a tag bumped on every update, which
+/// prevents ABA. Blocks grow geometrically and are only released on drop.
+#[derive(Debug)]
+pub struct ConcurrentBlockAllocator<N, A = Global>
+where
+    N: Nodable,
+    A: Allocator,
+{
+    alloc: A,
+    blocks: [AtomicPtr<Slot<N>>; MAX_BLOCKS],
+    block_size: usize,
+    head: AtomicU64,
+    cursor: AtomicUsize,
+    free_len: AtomicUsize,
+    live: AtomicUsize,
+    peak_live: AtomicUsize,
+}
+
+#[cfg(feature = "alloc")]
+impl<N> Default for ConcurrentBlockAllocator<N>
+where
+    N: Nodable,
+{
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_BLOCK_SIZE)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<N> ConcurrentBlockAllocator<N>
+where
+    N: Nodable,
+{
+    pub fn new(block_size: usize) -> Self {
+        Self::new_in(block_size, Global)
+    }
+}
+
+impl<N, A> ConcurrentBlockAllocator<N, A>
+where
+    N: Nodable,
+    A: Allocator,
+{
+    pub const DEFAULT_BLOCK_SIZE: usize = 256;
+
+    pub fn new_in(block_size: usize, alloc: A) -> Self {
+        assert!(block_size > 0, "invalid block size of 0");
+
+        Self {
+            alloc,
+            blocks: core::array::from_fn(|_| AtomicPtr::new(ptr::null_mut())),
+            block_size,
+            head: AtomicU64::new(0),
+            cursor: AtomicUsize::new(0),
+            free_len: AtomicUsize::new(0),
+            live: AtomicUsize::new(0),
+            peak_live: AtomicUsize::new(0),
+        }
+    }
+
+    /// The allocator that blocks are allocated with.
+    pub fn allocator(&self) -> &A {
+        &self.alloc
+    }
+
+    pub fn get_node(&self) -> *mut N {
+        self.try_get_node().unwrap_or_else(|err| err.handle())
+    }
+
+    pub fn try_get_node(&self) -> Result<*mut N, AllocError> {
+        let slot = match self.pop_free() {
+            Some(slot) => slot,
+            None => self.bump()?,
+        };
+        let node = slot as *mut N;
+        unsafe { ptr::write(node, Default::default()) };
+        let live = self.live.fetch_add(1, Ordering::Relaxed) + 1;
+        self.peak_live.fetch_max(live, Ordering::Relaxed);
+        Ok(node)
+    }
+
+    /// # Safety
+    ///
+    /// Returned node must come from this allocator and have its fields uninit/dropped
+    pub unsafe fn return_node(&self, node: *mut N) {
+        self.live.fetch_sub(1, Ordering::Relaxed);
+        unsafe { self.push_free(node as *mut Slot<N>) };
+    }
+
+    /// Allocates blocks until `n_nodes` nodes are available without allocating.
+    ///
+    /// Other threads may take these nodes in the meantime.
+    pub fn try_reserve(&self, n_nodes: usize) -> Result<(), AllocError> {
+        loop {
+            let installed = self
+                .blocks
+                .iter()
+                .take_while(|block| !block.load(Ordering::Acquire).is_null())
+                .count();
+            let capacity = 1usize
+                .checked_shl(installed as u32)
+                .map_or(usize::MAX, |blocks| {
+                    self.block_size.saturating_mul(blocks - 1)
+                });
+            let never_used = capacity.saturating_sub(self.cursor.load(Ordering::Relaxed));
+            if never_used + self.free_len.load(Ordering::Relaxed) >= n_nodes {
+                return Ok(());
+            }
+            if installed == MAX_BLOCKS {
+                return Err(AllocError::CapacityOverflow);
+            }
+            self.block(installed)?;
+        }
+    }
+
+    pub fn stats(&self) -> AllocatorStats {
+        let mut stats = AllocatorStats {
+            live_nodes: self.live.load(Ordering::Relaxed),
+            free_nodes: self.free_len.load(Ordering::Relaxed),
+            peak_live_nodes: self.peak_live.load(Ordering::Relaxed),
+            ..Default::default()
+        };
+        // Blocks are allocated in order
+        for (i, block) in self.blocks.iter().enumerate() {
+            if block.load(Ordering::Relaxed).is_null() {
+                break;
+            }
+            stats.blocks += 1;
+            stats.reserved_bytes += (self.block_size << i) * mem::size_of::<Slot<N>>();
+        }
+        stats
+    }
+
+    fn pop_free(&self) -> Option<*mut Slot<N>> {
+        let mut head = self.head.load(Ordering::Acquire);
+        loop {
+            let index = head as u32;
+            if index == 0 {
+                return None;
+            }
+            let slot = self.slot(index as usize - 1);
+            // The slot may be taken concurrently, then its link is stale and the tag has moved
+            let next = unsafe { (*slot).next.load(Ordering::Relaxed) };
+            let new_head = (head >> 32).wrapping_add(1) << 32 | next as u64;
+            match self.head.compare_exchange_weak(
+                head,
+                new_head,
+                Ordering::Acquire,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => {
+                    self.free_len.fetch_sub(1, Ordering::Relaxed);
+                    return Some(slot);
+                }
+                Err(current) => head = current,
+            }
+        }
+    }
+
+    unsafe fn push_free(&self, slot: *mut Slot<N>) {
+        let index = unsafe { (*slot).index };
+        // Counted before being published, so that concurrent pops never underflow
+        self.free_len.fetch_add(1, Ordering::Relaxed);
+        let mut head = self.head.load(Ordering::Relaxed);
+        loop {
+            unsafe { (*slot).next.store(head as u32, Ordering::Relaxed) };
+            let new_head = (head >> 32).wrapping_add(1) << 32 | (index + 1) as u64;
+            match self.head.compare_exchange_weak(
+                head,
+                new_head,
+                Ordering::Release,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return,
+                Err(current) => head = current,
+            }
+        }
+    }
+
+    /// Hands out the next slot that was never used.
+    fn bump(&self) -> Result<*mut Slot<N>, AllocError> {
+        let mut cursor = self.cursor.load(Ordering::Relaxed);
+        loop {
+            // Slot indices are stored (plus one) in 32 bits
+            if cursor >= u32::MAX as usize {
+                return Err(AllocError::CapacityOverflow);
+            }
+            let (block, offset) = self.locate(cursor);
+            let slots = self.block(block)?;
+            match self.cursor.compare_exchange_weak(
+                cursor,
+                cursor + 1,
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return Ok(unsafe { slots.add(offset) }),
+                Err(current) => cursor = current,
+            }
+        }
+    }
+
+    /// Gets block `i`, allocating it if no other thread did so yet.
+    fn block(&self, i: usize) -> Result<*mut Slot<N>, AllocError> {
+        let slots = self.blocks[i].load(Ordering::Acquire);
+        if !slots.is_null() {
+            return Ok(slots);
+        }
+        let layout = self.block_layout(i)?;
+        let new_slots = self.alloc.allocate(layout)?.as_ptr() as *mut Slot<N>;
+        let first_index = self.block_size * ((1 << i) - 1);
+        for offset in 0..self.block_size << i {
+            unsafe {
+                let slot = new_slots.add(offset);
+                ptr::addr_of_mut!((*slot).next).write(AtomicU32::new(0));
+                // Indices past u32::MAX are never handed out
+                ptr::addr_of_mut!((*slot).index).write((first_index + offset) as u32);
+            }
+        }
+        match self.blocks[i].compare_exchange(
+            ptr::null_mut(),
+            new_slots,
+            Ordering::AcqRel,
+            Ordering::Acquire,
+        ) {
+            Ok(_) => Ok(new_slots),
+            Err(slots) => {
+                unsafe {
+                    self.alloc
+                        .deallocate(NonNull::new_unchecked(new_slots as *mut u8), layout)
+                };
+                Ok(slots)
+            }
+        }
+    }
+
+    fn block_layout(&self, i: usize) -> Result<Layout, AllocError> {
+        let len = self
+            .block_size
+            .checked_mul(1 << i)
+            .ok_or(AllocError::CapacityOverflow)?;
+        Layout::array::<Slot<N>>(len).map_err(|_| AllocError::CapacityOverflow)
+    }
+
+    /// Block and offset of the slot at `index`.
+    fn locate(&self, index: usize) -> (usize, usize) {
+        let block = (index / self.block_size + 1).ilog2() as usize;
+        (block, index - self.block_size * ((1 << block) - 1))
+    }
+
+    fn slot(&self, index: usize) -> *mut Slot<N> {
+        let (block, offset) = self.locate(index);
+        unsafe { self.blocks[block].load(Ordering::Acquire).add(offset) }
+    }
+}
+
+impl<N, A> Drop for ConcurrentBlockAllocator<N, A>
+where
+    N: Nodable,
+    A: Allocator,
+{
+    fn drop(&mut self) {
+        for (i, block) in self.blocks.iter().enumerate() {
+            let slots = block.load(Ordering::Relaxed);
+            if slots.is_null() {
+                break;
+            }
+            let layout = self.block_layout(i).unwrap();
+            unsafe {
+                self.alloc
+                    .deallocate(NonNull::new_unchecked(slots as *mut u8), layout)
+            };
+        }
+    }
+}
+
+/// Containers can share a concurrent allocator by reference, even across threads.
+unsafe impl<N, A> NodePool<N> for &ConcurrentBlockAllocator<N, A>
+where
+    N: Nodable,
+    A: Allocator,
+{
+    fn try_get_node(&mut self) -> Result<*mut N, AllocError> {
+        ConcurrentBlockAllocator::try_get_node(self)
+    }
+
+    unsafe fn return_node(&mut self, node: *mut N) {
+        unsafe { ConcurrentBlockAllocator::return_node(self, node) }
+    }
+
+    fn try_reserve(&mut self, n_nodes: usize) -> Result<(), AllocError> {
+        ConcurrentBlockAllocator::try_reserve(self, n_nodes)
+    }
+
+    /// Blocks are only released on drop, as other threads may still read stale slots.
+    fn shrink_to_fit(&mut self) {}
+
+    fn stats(&self) -> AllocatorStats {
+        ConcurrentBlockAllocator::stats(self)
+    }
+
+    fn shares_nodes_with(&self, other: &Self) -> bool {
+        ptr::eq(*self, *other)
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use super::*;
+    use crate::allocator::Node;
+
+    #[test]
+    #[cfg(not(loom))]
+    fn concurrent_block_allocator_ok() {
+        let allocator = ConcurrentBlockAllocator::<Node<usize>>::new(2);
+        let nodes: [_; 7] = core::array::from_fn(|_| allocator.get_node());
+        let stats = allocator.stats();
+        assert_eq!(3, stats.blocks);
+        assert_eq!(7, stats.live_nodes);
+        assert_eq!(
+            14 * mem::size_of::<Slot<Node<usize>>>(),
+            stats.reserved_bytes
+        );
+
+        for &node in &nodes {
+            let index = unsafe { (*(node as *mut Slot<Node<usize>>)).index };
+            assert_eq!(node, allocator.slot(index as usize) as *mut _);
+        }
+        unsafe {
+            allocator.return_node(nodes[2]);
+            allocator.return_node(nodes[5]);
+        }
+        assert_eq!(nodes[5], allocator.get_node());
+        assert_eq!(nodes[2], allocator.get_node());
+        assert_eq!(7, allocator.stats().peak_live_nodes);
+
+        allocator.try_reserve(20).unwrap();
+        assert_eq!(4, allocator.stats().blocks);
+    }
+
+    #[test]
+    #[cfg(not(loom))]
+    fn concurrent_block_allocator_threads() {
+        use crate::stacks::LinkedListStack;
+
+        let allocator = ConcurrentBlockAllocator::<Node<usize>>::new(4);
+        std::thread::scope(|scope| {
+            for i in 0..4 {
+                let allocator = &allocator;
+                scope.spawn(move || {
+                    let mut stack = LinkedListStack::with_allocator(allocator);
+                    for round in 0..10 {
+                        for j in 0..10 {
+                            stack.push(i * round * j);
+                        }
+                        let sum: usize = (0..10).map(|_| stack.pop()).sum();
+                        assert_eq!(i * round * 45, sum);
+                    }
+                });
+            }
+        });
+        let stats = allocator.stats();
+        assert_eq!(0, stats.live_nodes);
+        assert!(stats.free_nodes <= 40);
+    }
+
+    #[test]
+    #[cfg(loom)]
+    fn concurrent_block_allocator_loom() {
+        use loom::sync::Arc;
+
+        // Bounding preemptions keeps the exploration tractable
+        let mut model = loom::model::Builder::new();
+        model.preemption_bound = Some(2);
+        model.check(|| {
+            let allocator = Arc::new(ConcurrentBlockAllocator::<Node<usize>>::new(2));
+            let nodes = [allocator.get_node(), allocator.get_node()];
+            for node in nodes {
+                unsafe { allocator.return_node(node) };
+            }
+
+            // A thread popping both nodes and pushing the first one back is where ABA strikes
+            let threads: [_; 2] = core::array::from_fn(|i| {
+                let allocator = Arc::clone(&allocator);
+                loom::thread::spawn(move || unsafe {
+                    let nodes = [allocator.get_node(), allocator.get_node()];
+                    allocator.return_node(nodes[0]);
+                    (*nodes[1]).val = MaybeUninit::new(i);
+                    assert_eq!(i, (*nodes[1]).val.assume_init_read());
+                    allocator.return_node(nodes[1]);
+                })
+            });
+            for thread in threads {
+                thread.join().unwrap();
+            }
+            assert_eq!(0, allocator.stats().live_nodes);
+            let free = allocator.cursor.load(Ordering::Relaxed);
+            assert_eq!(free, allocator.stats().free_nodes);
+            let nodes: [_; 4] = core::array::from_fn(|i| {
+                if i < free {
+                    allocator.get_node()
+                } else {
+                    ptr::null_mut()
+                }
+            });
+            for i in 0..free {
+                assert!(
+                    !nodes[i + 1..free].contains(&nodes[i]),
+                    "node handed out twice"
+                );
+            }
+        });
+    }
+}
@@ -0,0 +1,1465 @@
+#[cfg(feature = "alloc")]
+use alloc::alloc::{alloc, dealloc, handle_alloc_error};
+#[cfg(feature = "alloc")]
+use alloc::rc::Rc;
+#[cfg(feature = "sync")]
+use alloc::sync::Arc;
+use core::alloc::Layout;
+use core::cell::Cell;
+#[cfg(feature = "alloc")]
+use core::cell::RefCell;
+use core::fmt;
+use core::mem::{self, MaybeUninit};
+use core::ptr::{self, NonNull};
+use core::slice;
+
+#[cfg(target_has_atomic = "ptr")]
+mod arena;
+#[cfg(target_has_atomic = "64")]
+mod concurrent;
+
+#[cfg(target_has_atomic = "ptr")]
+pub use arena::{NodeArena, NodeHandle};
+#[cfg(target_has_atomic = "64")]
+pub use concurrent::ConcurrentBlockAllocator;
+
+/// Derives [`Nodable`](trait@Nodable) and `Default` for a struct, see the
+/// [`octads-derive`](octads_derive) crate.
+#[cfg(feature = "derive")]
+pub use octads_derive::Nodable;
+
+pub trait Nodable: Default {
+    fn next(&self) -> *mut Self;
+    fn next_mut(&mut self) -> &mut *mut Self;
+}
+
+/// The error returned by fallible allocations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AllocError {
+    /// The requested memory layout exceeds `isize::MAX` bytes.
+    CapacityOverflow,
+    /// The underlying allocator returned an error for this layout.
+    AllocFailed { layout: Layout },
+}
+
+impl AllocError {
+    /// Diverges the same way as infallible allocations do.
+    pub(crate) fn handle(self) -> ! {
+        match self {
+            Self::CapacityOverflow => panic!("capacity overflow"),
+            #[cfg(feature = "alloc")]
+            Self::AllocFailed { layout } => handle_alloc_error(layout),
+            #[cfg(not(feature = "alloc"))]
+            Self::AllocFailed { layout } => {
+                panic!("memory allocation of {} bytes failed", layout.size())
+            }
+        }
+    }
+}
+
+impl fmt::Display for AllocError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::CapacityOverflow => f.write_str("memory layout exceeds isize::MAX bytes"),
+            Self::AllocFailed { layout } => {
+                write!(f, "memory allocation of {} bytes failed", layout.size())
+            }
+        }
+    }
+}
+
+impl core::error::Error for AllocError {}
+
+/// A stable subset of the (unstable) `core::alloc::Allocator` trait.
+///
+/// # Safety
+///
+/// Memory blocks returned by [`allocate`](Allocator::allocate) must stay valid until they
+/// are given back to [`deallocate`](Allocator::deallocate), or until the allocator is
+/// dropped. Blocks of zero size may be dangling but must be aligned.
+pub unsafe trait Allocator {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<u8>, AllocError>;
+
+    /// # Safety
+    ///
+    /// `ptr` must have been allocated by this allocator with the same `layout`.
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout);
+}
+
+unsafe impl<A> Allocator for &A
+where
+    A: Allocator + ?Sized,
+{
+    fn allocate(&self, layout: Layout) -> Result<NonNull<u8>, AllocError> {
+        (**self).allocate(layout)
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        unsafe { (**self).deallocate(ptr, layout) }
+    }
+}
+
+/// The global memory allocator.
+///
+/// Without the `alloc` feature, allocations of non-zero size always fail.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Global;
+
+unsafe impl Allocator for Global {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<u8>, AllocError> {
+        if layout.size() == 0 {
+            return Ok(dangling(layout));
+        }
+        #[cfg(feature = "alloc")]
+        let ptr = unsafe { alloc(layout) };
+        #[cfg(not(feature = "alloc"))]
+        let ptr = ptr::null_mut();
+        NonNull::new(ptr).ok_or(AllocError::AllocFailed { layout })
+    }
+
+    #[cfg_attr(not(feature = "alloc"), allow(unused_variables))]
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        #[cfg(feature = "alloc")]
+        if layout.size() != 0 {
+            unsafe { dealloc(ptr.as_ptr(), layout) }
+        }
+    }
+}
+
+/// Bump allocator over a caller-supplied memory region.
+///
+/// Deallocating only gives memory back when it is the last allocated block.
+#[derive(Debug)]
+pub struct Region {
+    cursor: Cell<*mut u8>,
+    end: *mut u8,
+}
+
+impl Region {
+    pub fn new(region: &'static mut [MaybeUninit<u8>]) -> Self {
+        let range = region.as_mut_ptr_range();
+        Self {
+            cursor: Cell::new(range.start as *mut u8),
+            end: range.end as *mut u8,
+        }
+    }
+
+    /// Number of bytes left in the region, regardless of alignment.
+    pub fn remaining(&self) -> usize {
+        self.end as usize - self.cursor.get() as usize
+    }
+}
+
+unsafe impl Allocator for Region {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<u8>, AllocError> {
+        if layout.size() == 0 {
+            return Ok(dangling(layout));
+        }
+        let cursor = self.cursor.get();
+        let padding = cursor.align_offset(layout.align());
+        if padding == usize::MAX || self.remaining() < padding + layout.size() {
+            return Err(AllocError::AllocFailed { layout });
+        }
+        let ptr = unsafe { cursor.add(padding) };
+        self.cursor.set(unsafe { ptr.add(layout.size()) });
+        Ok(unsafe { NonNull::new_unchecked(ptr) })
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        if layout.size() != 0 && unsafe { ptr.as_ptr().add(layout.size()) } == self.cursor.get() {
+            self.cursor.set(ptr.as_ptr());
+        }
+    }
+}
+
+/// Size of a cache line on most current CPUs, see [`BlockAllocator::with_node_align`].
+pub const CACHE_LINE_SIZE: usize = 64;
+
+/// Byte pattern that free nodes are filled with, with the `debug-alloc` feature.
+#[cfg(feature = "debug-alloc")]
+const POISON: u8 = 0xA5;
+
+fn dangling(layout: Layout) -> NonNull<u8> {
+    unsafe { NonNull::new_unchecked(ptr::without_provenance_mut(layout.align())) }
+}
+
+#[derive(Debug)]
+pub struct BlockAllocator<N, A = Global>
+where
+    N: Nodable,
+    A: Allocator,
+{
+    alloc: A,
+    blocks: *mut *mut N,
+    blocks_cap: usize,
+    blocks_len: usize,
+    /// Whether the blocks array must not be reallocated, see [`BlockAllocator::from_region`].
+    fixed_blocks: bool,
+    cursor: *mut N,
+    block_size: usize,
+    block_align: usize,
+    node_stride: usize,
+    size_left: usize,
+    free_list: *mut N,
+    free_len: usize,
+    live: usize,
+    peak_live: usize,
+    #[cfg(feature = "debug-alloc")]
+    next_offset: usize,
+}
+
+/// A snapshot of a [`BlockAllocator`]'s usage, see [`BlockAllocator::stats`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AllocatorStats {
+    /// Blocks currently allocated.
+    pub blocks: usize,
+    /// Nodes handed out and not returned yet.
+    pub live_nodes: usize,
+    /// Nodes waiting on the free list.
+    pub free_nodes: usize,
+    /// Highest value ever reached by `live_nodes`.
+    pub peak_live_nodes: usize,
+    /// Bytes held from the underlying allocator, blocks and bookkeeping included.
+    pub reserved_bytes: usize,
+}
+
+#[cfg(feature = "alloc")]
+impl<N> Default for BlockAllocator<N>
+where
+    N: Nodable,
+{
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_BLOCK_SIZE, Self::DEFAULT_BLOCK_CAP)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<N> BlockAllocator<N>
+where
+    N: Nodable,
+{
+    pub fn new(block_size: usize, blocks_cap: usize) -> Self {
+        Self::new_in(block_size, blocks_cap, Global)
+    }
+
+    pub fn try_new(block_size: usize, blocks_cap: usize) -> Result<Self, AllocError> {
+        Self::try_new_in(block_size, blocks_cap, Global)
+    }
+}
+
+impl<N> BlockAllocator<N, Region>
+where
+    N: Nodable,
+{
+    /// Creates an allocator that carves its blocks (and their bookkeeping) out of `region`
+    /// instead of using the global allocator.
+    ///
+    /// Once `region` is exhausted, `try_*` methods return an [`AllocError`] while other
+    /// methods panic.
+    ///
+    /// The blocks array is allocated up front and never grows, as a [`Region`] can only
+    /// reclaim its topmost allocation: at most `blocks_cap` blocks are ever allocated.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `region` cannot hold the blocks array.
+    pub fn from_region(
+        region: &'static mut [MaybeUninit<u8>],
+        block_size: usize,
+        blocks_cap: usize,
+    ) -> Self {
+        let mut allocator = Self::with_alloc(Region::new(region), block_size, blocks_cap);
+        allocator.grow_blocks().unwrap_or_else(|err| err.handle());
+        allocator.fixed_blocks = true;
+        allocator
+    }
+}
+
+impl<N, A> BlockAllocator<N, A>
+where
+    N: Nodable,
+    A: Allocator,
+{
+    pub const DEFAULT_BLOCK_SIZE: usize = 256;
+    pub const DEFAULT_BLOCK_CAP: usize = 32;
+
+    pub fn new_in(block_size: usize, blocks_cap: usize, alloc: A) -> Self {
+        Self::try_new_in(block_size, blocks_cap, alloc).unwrap_or_else(|err| err.handle())
+    }
+
+    pub fn try_new_in(block_size: usize, blocks_cap: usize, alloc: A) -> Result<Self, AllocError> {
+        let mut allocator = Self::with_alloc(alloc, block_size, blocks_cap);
+        allocator.grow_blocks()?;
+        Ok(allocator)
+    }
+
+    fn with_alloc(alloc: A, block_size: usize, blocks_cap: usize) -> Self {
+        assert!(block_size > 0, "invalid block size of 0");
+        assert!(blocks_cap > 0, "invalid blocks capacity of 0");
+
+        Self {
+            alloc,
+            blocks: ptr::null_mut(),
+            blocks_len: 0,
+            blocks_cap,
+            fixed_blocks: false,
+            cursor: ptr::null_mut(),
+            block_size,
+            block_align: mem::align_of::<N>(),
+            node_stride: mem::size_of::<N>(),
+            size_left: 0,
+            free_list: ptr::null_mut(),
+            free_len: 0,
+            live: 0,
+            peak_live: 0,
+            #[cfg(feature = "debug-alloc")]
+            next_offset: {
+                let mut probe = N::default();
+                let base = &probe as *const N as usize;
+                probe.next_mut() as *mut *mut N as usize - base
+            },
+        }
+    }
+
+    /// The allocator that blocks are allocated with.
+    pub fn allocator(&self) -> &A {
+        &self.alloc
+    }
+
+    /// Aligns blocks to `align` bytes, e.g. [`CACHE_LINE_SIZE`] or the huge page size.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `align` is not a power of two, or if nodes were already allocated.
+    pub fn with_block_align(mut self, align: usize) -> Self {
+        assert!(
+            align.is_power_of_two(),
+            "invalid block alignment of {align}"
+        );
+        assert!(
+            self.blocks_len == 0,
+            "block alignment set after allocating nodes"
+        );
+        self.block_align = self.block_align.max(align);
+        self
+    }
+
+    /// Pads and aligns each node to `align` bytes, so that nodes never share a cache line
+    /// when `align` is [`CACHE_LINE_SIZE`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `align` is not a power of two, or if nodes were already allocated.
+    pub fn with_node_align(mut self, align: usize) -> Self {
+        assert!(align.is_power_of_two(), "invalid node alignment of {align}");
+        assert!(
+            self.blocks_len == 0,
+            "node alignment set after allocating nodes"
+        );
+        self.node_stride = self.node_stride.next_multiple_of(align);
+        self.block_align = self.block_align.max(align);
+        self
+    }
+
+    pub fn get_node(&mut self) -> *mut N {
+        self.try_get_node().unwrap_or_else(|err| err.handle())
+    }
+
+    pub fn try_get_node(&mut self) -> Result<*mut N, AllocError> {
+        let node;
+        if !self.free_list.is_null() {
+            node = self.free_list;
+            #[cfg(feature = "debug-alloc")]
+            self.check_poison(node);
+            self.free_list = unsafe { self.next_of(node) };
+            self.free_len -= 1;
+        } else {
+            if self.cursor.is_null() || self.size_left == 0 {
+                self.cursor = self.alloc_block()?;
+                self.size_left = self.block_size;
+            }
+            node = self.cursor;
+            self.cursor = unsafe { self.cursor.byte_add(self.node_stride) };
+            self.size_left -= 1;
+        }
+        #[cfg(feature = "debug-alloc")]
+        self.set_in_use(node, true);
+        unsafe { ptr::write(node, Default::default()) };
+        self.live += 1;
+        self.peak_live = self.peak_live.max(self.live);
+        Ok(node)
+    }
+
+    pub fn get_chain(&mut self, n: usize) -> (*mut N, *mut N) {
+        self.try_get_chain(n).unwrap_or_else(|err| err.handle())
+    }
+
+    /// Hands out `n` nodes linked through [`Nodable::next_mut`], the last one linking to null.
+    ///
+    /// Returns the first and last nodes of the chain, both null if `n` is 0.
+    pub fn try_get_chain(&mut self, n: usize) -> Result<(*mut N, *mut N), AllocError> {
+        self.try_reserve(n)?;
+        let from_free = n.min(self.free_len);
+        // The first node taken ends up last
+        let tail = match n {
+            0 => ptr::null_mut(),
+            _ if from_free > 0 => self.free_list,
+            _ => self.cursor,
+        };
+        let mut head = ptr::null_mut();
+        for _ in 0..from_free {
+            let node = self.free_list;
+            #[cfg(feature = "debug-alloc")]
+            self.check_poison(node);
+            self.free_list = unsafe { self.next_of(node) };
+            head = self.prepend(node, head);
+        }
+        self.free_len -= from_free;
+        // The reservation left enough nodes in the current block
+        let from_block = n - from_free;
+        for _ in 0..from_block {
+            head = self.prepend(self.cursor, head);
+            self.cursor = unsafe { self.cursor.byte_add(self.node_stride) };
+        }
+        self.size_left -= from_block;
+        self.live += n;
+        self.peak_live = self.peak_live.max(self.live);
+        Ok((head, tail))
+    }
+
+    /// Makes sure that the next `n_nodes` calls to [`get_node`](Self::get_node) will not
+    /// allocate.
+    pub fn try_reserve(&mut self, n_nodes: usize) -> Result<(), AllocError> {
+        let mut available = self.free_len + self.size_left;
+        while available < n_nodes {
+            let block = self.alloc_block()?;
+            for i in (0..self.block_size).rev() {
+                unsafe {
+                    let node = block.byte_add(i * self.node_stride);
+                    ptr::write(node, Default::default());
+                    self.push_free(node);
+                }
+            }
+            available += self.block_size;
+        }
+        Ok(())
+    }
+
+    /// # Safety
+    ///
+    /// Returned node must have its fields uninit/dropped
+    ///
+    /// # Panics
+    ///
+    /// With the `debug-alloc` feature, panics if `node` does not come from this allocator
+    /// or if it was already returned.
+    pub unsafe fn return_node(&mut self, node: *mut N) {
+        #[cfg(feature = "debug-alloc")]
+        if !self.set_in_use(node, false) {
+            panic!("double return: node {node:p} was already returned");
+        }
+        unsafe { self.push_free(node) };
+        self.live -= 1;
+    }
+
+    /// Initializes `node` as handed out and links it in front of `head`.
+    fn prepend(&mut self, node: *mut N, head: *mut N) -> *mut N {
+        #[cfg(feature = "debug-alloc")]
+        self.set_in_use(node, true);
+        unsafe {
+            ptr::write(node, Default::default());
+            *(*node).next_mut() = head;
+        }
+        node
+    }
+
+    /// Returns `len` nodes linked through [`Nodable::next_mut`] from `head` to `tail`.
+    ///
+    /// The chain is spliced into the free list in O(1), its length is only needed to keep
+    /// [`stats`](Self::stats) exact.
+    ///
+    /// # Safety
+    ///
+    /// Returned nodes must have their fields uninit/dropped, and `len` must be the number of
+    /// nodes from `head` to `tail`.
+    pub unsafe fn return_chain(&mut self, head: *mut N, tail: *mut N, len: usize) {
+        if len == 0 {
+            return;
+        }
+        // Each node is checked, which walks the chain anyway
+        #[cfg(feature = "debug-alloc")]
+        {
+            let mut node = head;
+            for i in 1..=len {
+                let next = unsafe { (*node).next() };
+                if i == len && node != tail {
+                    panic!("chain length mismatch: {tail:p} is not the node number {len}");
+                }
+                unsafe { self.return_node(node) };
+                node = next;
+            }
+        }
+        #[cfg(not(feature = "debug-alloc"))]
+        {
+            unsafe { self.set_next(tail, self.free_list) };
+            self.free_list = head;
+            self.free_len += len;
+            self.live -= len;
+        }
+    }
+
+    unsafe fn push_free(&mut self, node: *mut N) {
+        #[cfg(feature = "debug-alloc")]
+        unsafe {
+            ptr::write_bytes(node as *mut u8, POISON, mem::size_of::<N>())
+        };
+        unsafe { self.set_next(node, self.free_list) };
+        self.free_list = node;
+        self.free_len += 1;
+    }
+
+    /// # Safety
+    ///
+    /// `node` must be on the free list.
+    unsafe fn next_of(&self, node: *mut N) -> *mut N {
+        // Free nodes are poisoned, except their link, so they must not be referenced
+        #[cfg(feature = "debug-alloc")]
+        return unsafe {
+            (node as *mut u8)
+                .add(self.next_offset)
+                .cast::<*mut N>()
+                .read()
+        };
+        #[cfg(not(feature = "debug-alloc"))]
+        return unsafe { (*node).next() };
+    }
+
+    /// # Safety
+    ///
+    /// `node` must be a free node.
+    unsafe fn set_next(&mut self, node: *mut N, next: *mut N) {
+        #[cfg(feature = "debug-alloc")]
+        unsafe {
+            (node as *mut u8)
+                .add(self.next_offset)
+                .cast::<*mut N>()
+                .write(next)
+        };
+        #[cfg(not(feature = "debug-alloc"))]
+        unsafe {
+            *(*node).next_mut() = next
+        };
+    }
+
+    pub fn stats(&self) -> AllocatorStats {
+        let blocks_bytes = if self.blocks.is_null() {
+            0
+        } else {
+            self.blocks_cap * mem::size_of::<*mut N>()
+        };
+        AllocatorStats {
+            blocks: self.blocks_len,
+            live_nodes: self.live,
+            free_nodes: self.free_len,
+            peak_live_nodes: self.peak_live,
+            reserved_bytes: self.blocks_len * self.block_layout().map_or(0, |layout| layout.size())
+                + blocks_bytes,
+        }
+    }
+
+    /// Whether `node` was handed out by this allocator and still lies in one of its blocks,
+    /// so that it can be read whether it is live or free.
+    pub fn owns(&self, node: *const N) -> bool {
+        let Some(i) = self.block_index(node) else {
+            return false;
+        };
+        let offset = node as usize - self.blocks()[i] as usize;
+        // The rest of the current block was never handed out, so it is uninit
+        let never_used = self.size_left > 0
+            && self.block_index(self.cursor) == Some(i)
+            && node >= self.cursor as *const N;
+        offset.is_multiple_of(self.node_stride) && !never_used
+    }
+
+    /// Gives back to the underlying allocator every block whose nodes are all free.
+    ///
+    /// This is a compaction pass over the whole free list, it does nothing if its own
+    /// bookkeeping cannot be allocated.
+    pub fn shrink_to_fit(&mut self) {
+        if self.blocks_len == 0 {
+            return;
+        }
+        let Ok(counts_layout) = Layout::array::<usize>(self.blocks_len) else {
+            return;
+        };
+        let Ok(counts_ptr) = self.alloc.allocate(counts_layout) else {
+            return;
+        };
+        let counts = unsafe {
+            let counts_ptr = counts_ptr.as_ptr() as *mut usize;
+            counts_ptr.write_bytes(0, self.blocks_len);
+            slice::from_raw_parts_mut(counts_ptr, self.blocks_len)
+        };
+
+        // Count free nodes per block, including the ones never handed out
+        if self.size_left > 0 {
+            counts[self.block_index(self.cursor).unwrap()] += self.size_left;
+        }
+        let mut node = self.free_list;
+        while !node.is_null() {
+            counts[self.block_index(node).unwrap()] += 1;
+            node = unsafe { self.next_of(node) };
+        }
+
+        // Unlink free nodes belonging to blocks about to be released
+        let mut prev: *mut N = ptr::null_mut();
+        let mut node = self.free_list;
+        while !node.is_null() {
+            let next = unsafe { self.next_of(node) };
+            if counts[self.block_index(node).unwrap()] == self.block_size {
+                if prev.is_null() {
+                    self.free_list = next;
+                } else {
+                    unsafe { self.set_next(prev, next) };
+                }
+                self.free_len -= 1;
+            } else {
+                prev = node;
+            }
+            node = next;
+        }
+        if self.size_left > 0 && counts[self.block_index(self.cursor).unwrap()] == self.block_size {
+            self.cursor = ptr::null_mut();
+            self.size_left = 0;
+        }
+
+        let layout = self.block_layout().unwrap();
+        let mut kept = 0;
+        for (i, &count) in counts.iter().enumerate() {
+            unsafe {
+                let block = *self.blocks.add(i);
+                if count == self.block_size {
+                    self.alloc
+                        .deallocate(NonNull::new_unchecked(block as *mut u8), layout);
+                } else {
+                    self.blocks.add(kept).write(block);
+                    kept += 1;
+                }
+            }
+        }
+        self.blocks_len = kept;
+        unsafe { self.alloc.deallocate(counts_ptr, counts_layout) };
+    }
+
+    fn alloc_block(&mut self) -> Result<*mut N, AllocError> {
+        if self.blocks.is_null() || self.blocks_len == self.blocks_cap {
+            self.grow_blocks()?;
+        }
+
+        let layout = self.block_layout()?;
+        let new_block = self.alloc.allocate(layout)?.as_ptr() as *mut N;
+        #[cfg(feature = "debug-alloc")]
+        unsafe {
+            self.bitmap(new_block)
+                .write_bytes(0, self.block_size.div_ceil(usize::BITS as usize))
+        };
+        // Keep blocks sorted by address so that nodes can be mapped back to their block
+        let i = self.blocks().partition_point(|&block| block < new_block);
+        unsafe {
+            let slot = self.blocks.add(i);
+            slot.copy_to(slot.add(1), self.blocks_len - i);
+            slot.write(new_block);
+        }
+        self.blocks_len += 1;
+        Ok(new_block)
+    }
+
+    /// Layout of a block, followed by its ownership bitmap with the `debug-alloc` feature.
+    fn block_layout(&self) -> Result<Layout, AllocError> {
+        let layout = self.nodes_layout()?;
+        #[cfg(feature = "debug-alloc")]
+        let layout = Layout::array::<usize>(self.block_size.div_ceil(usize::BITS as usize))
+            .and_then(|bitmap| layout.extend(bitmap))
+            .map_err(|_| AllocError::CapacityOverflow)?
+            .0;
+        Ok(layout)
+    }
+
+    /// Layout of the nodes of a block, each one taking `node_stride` bytes.
+    fn nodes_layout(&self) -> Result<Layout, AllocError> {
+        self.node_stride
+            .checked_mul(self.block_size)
+            .and_then(|size| Layout::from_size_align(size, self.block_align).ok())
+            .ok_or(AllocError::CapacityOverflow)
+    }
+
+    #[cfg(feature = "debug-alloc")]
+    fn bitmap(&self, block: *mut N) -> *mut usize {
+        let offset = self
+            .nodes_layout()
+            .unwrap()
+            .extend(Layout::new::<usize>())
+            .unwrap()
+            .1;
+        unsafe { (block as *mut u8).add(offset) as *mut usize }
+    }
+
+    /// Marks `node` as handed out (or not), returning whether its state changed.
+    #[cfg(feature = "debug-alloc")]
+    fn set_in_use(&mut self, node: *mut N, in_use: bool) -> bool {
+        let block = self
+            .block_index(node)
+            .map(|i| self.blocks()[i])
+            .filter(|&block| (node as usize - block as usize).is_multiple_of(self.node_stride))
+            .unwrap_or_else(|| {
+                panic!("foreign pointer: node {node:p} does not belong to this allocator")
+            });
+        let index = (node as usize - block as usize) / self.node_stride;
+        let bits = usize::BITS as usize;
+        let word = unsafe { &mut *self.bitmap(block).add(index / bits) };
+        let mask = 1 << (index % bits);
+        let changed = (*word & mask != 0) != in_use;
+        *word = if in_use { *word | mask } else { *word & !mask };
+        changed
+    }
+
+    /// Panics if `node` was written to since it was returned.
+    #[cfg(feature = "debug-alloc")]
+    fn check_poison(&self, node: *mut N) {
+        let bytes = unsafe { slice::from_raw_parts(node as *const u8, mem::size_of::<N>()) };
+        let link = self.next_offset..self.next_offset + mem::size_of::<*mut N>();
+        let is_poisoned = bytes
+            .iter()
+            .enumerate()
+            .all(|(i, &byte)| link.contains(&i) || byte == POISON);
+        if !is_poisoned {
+            panic!("use after return: node {node:p} was written to after being returned");
+        }
+    }
+
+    fn blocks(&self) -> &[*mut N] {
+        if self.blocks.is_null() {
+            return &[];
+        }
+        unsafe { slice::from_raw_parts(self.blocks, self.blocks_len) }
+    }
+
+    /// Index of the block that contains `node`, if any.
+    fn block_index(&self, node: *const N) -> Option<usize> {
+        let blocks = self.blocks();
+        let i = blocks.partition_point(|&block| block as *const N <= node);
+        if i == 0 {
+            return None;
+        }
+        let offset = (node as usize).wrapping_sub(blocks[i - 1] as usize);
+        (offset < self.block_size * self.node_stride).then_some(i - 1)
+    }
+
+    /// Allocates the blocks array, or doubles its capacity if it is already allocated.
+    fn grow_blocks(&mut self) -> Result<(), AllocError> {
+        let new_cap = if self.blocks.is_null() {
+            self.blocks_cap
+        } else {
+            self.blocks_cap
+                .checked_mul(2)
+                .ok_or(AllocError::CapacityOverflow)?
+        };
+        let new_layout =
+            Layout::array::<*mut N>(new_cap).map_err(|_| AllocError::CapacityOverflow)?;
+        if self.fixed_blocks {
+            return Err(AllocError::AllocFailed { layout: new_layout });
+        }
+        let blocks = self.alloc.allocate(new_layout)?.as_ptr() as *mut *mut N;
+        if !self.blocks.is_null() {
+            let old_layout = Layout::array::<*mut N>(self.blocks_cap).unwrap();
+            unsafe {
+                self.blocks.copy_to_nonoverlapping(blocks, self.blocks_len);
+                self.alloc
+                    .deallocate(NonNull::new_unchecked(self.blocks as *mut u8), old_layout);
+            }
+        }
+        self.blocks = blocks;
+        self.blocks_cap = new_cap;
+        Ok(())
+    }
+}
+
+impl<N, A> Drop for BlockAllocator<N, A>
+where
+    N: Nodable,
+    A: Allocator,
+{
+    fn drop(&mut self) {
+        if self.blocks.is_null() {
+            return;
+        }
+        for i in 0..self.blocks_len {
+            let layout = self.block_layout().unwrap();
+            unsafe {
+                let block = *self.blocks.add(i) as *mut u8;
+                self.alloc.deallocate(NonNull::new_unchecked(block), layout)
+            };
+        }
+        let layout = Layout::array::<*mut N>(self.blocks_cap).unwrap();
+        unsafe {
+            self.alloc
+                .deallocate(NonNull::new_unchecked(self.blocks as *mut u8), layout)
+        };
+    }
+}
+
+/// A source of nodes for the linked containers.
+///
+/// # Safety
+///
+/// Nodes handed out must be aligned, initialized with `N::default()` and not handed out
+/// again until they are returned. They must stay valid as long as the pool is alive, or as
+/// long as any pool it [shares nodes with](NodePool::shares_nodes_with) is.
+pub unsafe trait NodePool<N>
+where
+    N: Nodable,
+{
+    fn try_get_node(&mut self) -> Result<*mut N, AllocError>;
+
+    fn get_node(&mut self) -> *mut N {
+        self.try_get_node().unwrap_or_else(|err| err.handle())
+    }
+
+    /// # Safety
+    ///
+    /// Returned node must come from this pool and have its fields uninit/dropped
+    unsafe fn return_node(&mut self, node: *mut N);
+
+    /// Hands out `n` nodes linked through [`Nodable::next_mut`], the last one linking to null.
+    ///
+    /// Returns the first and last nodes of the chain, both null if `n` is 0.
+    fn try_get_chain(&mut self, n: usize) -> Result<(*mut N, *mut N), AllocError> {
+        self.try_reserve(n)?;
+        let mut head = ptr::null_mut();
+        let mut tail: *mut N = ptr::null_mut();
+        for _ in 0..n {
+            let node = self.try_get_node()?;
+            unsafe { *(*node).next_mut() = head };
+            if tail.is_null() {
+                tail = node;
+            }
+            head = node;
+        }
+        Ok((head, tail))
+    }
+
+    fn get_chain(&mut self, n: usize) -> (*mut N, *mut N) {
+        self.try_get_chain(n).unwrap_or_else(|err| err.handle())
+    }
+
+    /// Returns `len` nodes linked through [`Nodable::next_mut`] from `head` to `tail`.
+    ///
+    /// # Safety
+    ///
+    /// Returned nodes must come from this pool and have their fields uninit/dropped, and
+    /// `len` must be the number of nodes from `head` to `tail`.
+    unsafe fn return_chain(&mut self, head: *mut N, tail: *mut N, len: usize) {
+        let _ = tail;
+        let mut node = head;
+        for _ in 0..len {
+            let next = unsafe { (*node).next() };
+            unsafe { self.return_node(node) };
+            node = next;
+        }
+    }
+
+    fn try_reserve(&mut self, n_nodes: usize) -> Result<(), AllocError>;
+
+    fn shrink_to_fit(&mut self);
+
+    fn stats(&self) -> AllocatorStats;
+
+    /// Whether nodes handed out by `self` can be returned to `other`, so that containers
+    /// using these two pools can move nodes between each other.
+    fn shares_nodes_with(&self, other: &Self) -> bool {
+        ptr::eq(self, other)
+    }
+}
+
+unsafe impl<N, A> NodePool<N> for BlockAllocator<N, A>
+where
+    N: Nodable,
+    A: Allocator,
+{
+    fn try_get_node(&mut self) -> Result<*mut N, AllocError> {
+        BlockAllocator::try_get_node(self)
+    }
+
+    unsafe fn return_node(&mut self, node: *mut N) {
+        unsafe { BlockAllocator::return_node(self, node) }
+    }
+
+    fn try_get_chain(&mut self, n: usize) -> Result<(*mut N, *mut N), AllocError> {
+        BlockAllocator::try_get_chain(self, n)
+    }
+
+    unsafe fn return_chain(&mut self, head: *mut N, tail: *mut N, len: usize) {
+        unsafe { BlockAllocator::return_chain(self, head, tail, len) }
+    }
+
+    fn try_reserve(&mut self, n_nodes: usize) -> Result<(), AllocError> {
+        BlockAllocator::try_reserve(self, n_nodes)
+    }
+
+    fn shrink_to_fit(&mut self) {
+        BlockAllocator::shrink_to_fit(self)
+    }
+
+    fn stats(&self) -> AllocatorStats {
+        BlockAllocator::stats(self)
+    }
+}
+
+/// A [`NodePool`] that can tell the nodes it handed out apart from any other pointer.
+///
+/// This lets containers validate handles that may be stale. Pools shared across threads do
+/// not implement it, as reading a node that another thread reuses would race.
+///
+/// # Safety
+///
+/// [`owns`](OwningPool::owns) must only return `true` for nodes that this pool handed out,
+/// that can still be read whether live or free, and that no other thread writes to.
+pub unsafe trait OwningPool<N>: NodePool<N>
+where
+    N: Nodable,
+{
+    /// Whether `node` was handed out by this pool and can still be read, live or free.
+    fn owns(&self, node: *const N) -> bool;
+}
+
+unsafe impl<N, A> OwningPool<N> for BlockAllocator<N, A>
+where
+    N: Nodable,
+    A: Allocator,
+{
+    fn owns(&self, node: *const N) -> bool {
+        BlockAllocator::owns(self, node)
+    }
+}
+
+/// A [`BlockAllocator`] that several containers can draw their nodes from.
+///
+/// Clones share the same free list and blocks, which are released once every clone (and
+/// every container holding one) is dropped.
+#[cfg(feature = "alloc")]
+#[derive(Debug)]
+pub struct SharedPool<N, A = Global>
+where
+    N: Nodable,
+    A: Allocator,
+{
+    inner: Rc<RefCell<BlockAllocator<N, A>>>,
+}
+
+#[cfg(feature = "alloc")]
+impl<N> SharedPool<N>
+where
+    N: Nodable,
+{
+    pub fn new(block_size: usize, blocks_cap: usize) -> Self {
+        Self::new_in(block_size, blocks_cap, Global)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<N, A> SharedPool<N, A>
+where
+    N: Nodable,
+    A: Allocator,
+{
+    pub fn new_in(block_size: usize, blocks_cap: usize, alloc: A) -> Self {
+        Self::from_allocator(BlockAllocator::new_in(block_size, blocks_cap, alloc))
+    }
+
+    pub fn from_allocator(allocator: BlockAllocator<N, A>) -> Self {
+        Self {
+            inner: Rc::new(RefCell::new(allocator)),
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<N, A> Clone for SharedPool<N, A>
+where
+    N: Nodable,
+    A: Allocator,
+{
+    fn clone(&self) -> Self {
+        Self {
+            inner: Rc::clone(&self.inner),
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+unsafe impl<N, A> NodePool<N> for SharedPool<N, A>
+where
+    N: Nodable,
+    A: Allocator,
+{
+    fn try_get_node(&mut self) -> Result<*mut N, AllocError> {
+        self.inner.borrow_mut().try_get_node()
+    }
+
+    unsafe fn return_node(&mut self, node: *mut N) {
+        unsafe { self.inner.borrow_mut().return_node(node) }
+    }
+
+    fn try_get_chain(&mut self, n: usize) -> Result<(*mut N, *mut N), AllocError> {
+        self.inner.borrow_mut().try_get_chain(n)
+    }
+
+    unsafe fn return_chain(&mut self, head: *mut N, tail: *mut N, len: usize) {
+        unsafe { self.inner.borrow_mut().return_chain(head, tail, len) }
+    }
+
+    fn try_reserve(&mut self, n_nodes: usize) -> Result<(), AllocError> {
+        self.inner.borrow_mut().try_reserve(n_nodes)
+    }
+
+    fn shrink_to_fit(&mut self) {
+        self.inner.borrow_mut().shrink_to_fit()
+    }
+
+    fn stats(&self) -> AllocatorStats {
+        self.inner.borrow().stats()
+    }
+
+    fn shares_nodes_with(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.inner, &other.inner)
+    }
+}
+
+// Clones never leave the thread, so no other thread reuses their nodes
+#[cfg(feature = "alloc")]
+unsafe impl<N, A> OwningPool<N> for SharedPool<N, A>
+where
+    N: Nodable,
+    A: Allocator,
+{
+    fn owns(&self, node: *const N) -> bool {
+        self.inner.borrow().owns(node)
+    }
+}
+
+/// Same as [`SharedPool`] but clones can be sent to other threads.
+#[cfg(feature = "sync")]
+#[derive(Debug)]
+pub struct SyncSharedPool<N, A = Global>
+where
+    N: Nodable,
+    A: Allocator,
+{
+    inner: Arc<std::sync::Mutex<BlockAllocator<N, A>>>,
+}
+
+// The pool only owns free nodes, which hold no value, and the blocks themselves.
+#[cfg(feature = "sync")]
+unsafe impl<N, A> Send for SyncSharedPool<N, A>
+where
+    N: Nodable,
+    A: Allocator + Send,
+{
+}
+
+#[cfg(feature = "sync")]
+unsafe impl<N, A> Sync for SyncSharedPool<N, A>
+where
+    N: Nodable,
+    A: Allocator + Send,
+{
+}
+
+#[cfg(feature = "sync")]
+impl<N> SyncSharedPool<N>
+where
+    N: Nodable,
+{
+    pub fn new(block_size: usize, blocks_cap: usize) -> Self {
+        Self::new_in(block_size, blocks_cap, Global)
+    }
+}
+
+#[cfg(feature = "sync")]
+impl<N, A> SyncSharedPool<N, A>
+where
+    N: Nodable,
+    A: Allocator,
+{
+    pub fn new_in(block_size: usize, blocks_cap: usize, alloc: A) -> Self {
+        Self::from_allocator(BlockAllocator::new_in(block_size, blocks_cap, alloc))
+    }
+
+    pub fn from_allocator(allocator: BlockAllocator<N, A>) -> Self {
+        Self {
+            inner: Arc::new(std::sync::Mutex::new(allocator)),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BlockAllocator<N, A>> {
+        // Pool operations cannot panic halfway, so a poisoned pool is still consistent
+        self.inner
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
+
+#[cfg(feature = "sync")]
+impl<N, A> Clone for SyncSharedPool<N, A>
+where
+    N: Nodable,
+    A: Allocator,
+{
+    fn clone(&self) -> Self {
+        Self {
+            inner: Arc::clone(&self.inner),
+        }
+    }
+}
+
+#[cfg(feature = "sync")]
+unsafe impl<N, A> NodePool<N> for SyncSharedPool<N, A>
+where
+    N: Nodable,
+    A: Allocator,
+{
+    fn try_get_node(&mut self) -> Result<*mut N, AllocError> {
+        self.lock().try_get_node()
+    }
+
+    unsafe fn return_node(&mut self, node: *mut N) {
+        unsafe { self.lock().return_node(node) }
+    }
+
+    fn try_get_chain(&mut self, n: usize) -> Result<(*mut N, *mut N), AllocError> {
+        self.lock().try_get_chain(n)
+    }
+
+    unsafe fn return_chain(&mut self, head: *mut N, tail: *mut N, len: usize) {
+        unsafe { self.lock().return_chain(head, tail, len) }
+    }
+
+    fn try_reserve(&mut self, n_nodes: usize) -> Result<(), AllocError> {
+        self.lock().try_reserve(n_nodes)
+    }
+
+    fn shrink_to_fit(&mut self) {
+        self.lock().shrink_to_fit()
+    }
+
+    fn stats(&self) -> AllocatorStats {
+        self.lock().stats()
+    }
+
+    fn shares_nodes_with(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.inner, &other.inner)
+    }
+}
+
+#[derive(Debug)]
+pub struct Node<T> {
+    pub next: *mut Node<T>,
+    pub val: MaybeUninit<T>,
+}
+
+impl<T> Default for Node<T> {
+    fn default() -> Self {
+        Self {
+            next: ptr::null_mut(),
+            val: MaybeUninit::uninit(),
+        }
+    }
+}
+
+impl<T> Nodable for Node<T> {
+    fn next(&self) -> *mut Self {
+        self.next
+    }
+
+    fn next_mut(&mut self) -> &mut *mut Self {
+        &mut self.next
+    }
+}
+
+#[derive(Debug)]
+pub struct BiNode<T> {
+    pub next: *mut BiNode<T>,
+    pub prev: *mut BiNode<T>,
+    /// Identifies the value for handles to it, 0 when no handle was given out.
+    pub(crate) stamp: u64,
+    pub val: MaybeUninit<T>,
+}
+
+impl<T> Default for BiNode<T> {
+    fn default() -> Self {
+        Self {
+            next: ptr::null_mut(),
+            prev: ptr::null_mut(),
+            stamp: 0,
+            val: MaybeUninit::uninit(),
+        }
+    }
+}
+
+impl<T> Nodable for BiNode<T> {
+    fn next(&self) -> *mut Self {
+        self.next
+    }
+
+    fn next_mut(&mut self) -> &mut *mut Self {
+        &mut self.next
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use super::*;
+    use crate::stacks::LinkedListStack;
+
+    #[test]
+    fn block_allocator_shrink_to_fit() {
+        let mut allocator: BlockAllocator<Node<usize>> = BlockAllocator::new(4, 1);
+        let nodes: [_; 10] = core::array::from_fn(|_| allocator.get_node());
+        assert_eq!(3, allocator.blocks_len);
+
+        // First block is only partly free, last block is partly untouched
+        for &node in &nodes[1..] {
+            unsafe { allocator.return_node(node) };
+        }
+        allocator.shrink_to_fit();
+        assert_eq!(1, allocator.blocks_len);
+        assert_eq!(3, allocator.free_len);
+        assert_eq!(Some(0), allocator.block_index(nodes[0]));
+        assert_eq!(None, allocator.block_index(nodes[9]));
+
+        let node = allocator.get_node();
+        assert_eq!(Some(0), allocator.block_index(node));
+        unsafe {
+            allocator.return_node(node);
+            allocator.return_node(nodes[0]);
+        }
+        allocator.shrink_to_fit();
+        assert_eq!(0, allocator.blocks_len);
+        assert_eq!(0, allocator.free_len);
+        assert_eq!(0, allocator.stats().live_nodes);
+        let node = allocator.get_node();
+        assert_eq!(Some(0), allocator.block_index(node));
+    }
+
+    #[test]
+    fn block_allocator_owns() {
+        let mut allocator: BlockAllocator<Node<u64>> = BlockAllocator::new(4, 1);
+        let nodes: [_; 2] = core::array::from_fn(|_| allocator.get_node());
+        assert!(allocator.owns(nodes[0]));
+        unsafe { allocator.return_node(nodes[1]) };
+        assert!(allocator.owns(nodes[1]));
+        // Never handed out, or not on a node boundary
+        assert!(!allocator.owns(unsafe { nodes[1].add(1) }));
+        assert!(!allocator.owns(unsafe { nodes[0].byte_add(1) }));
+        assert!(!allocator.owns(&Node::default()));
+
+        unsafe { allocator.return_node(nodes[0]) };
+        allocator.shrink_to_fit();
+        assert!(!allocator.owns(nodes[0]));
+    }
+
+    #[test]
+    fn block_allocator_stats() {
+        let mut allocator: BlockAllocator<Node<u64>> = BlockAllocator::new(4, 2);
+        let block_size = allocator.block_layout().unwrap().size();
+        let ptr_size = mem::size_of::<*mut Node<u64>>();
+        assert_eq!(
+            AllocatorStats {
+                reserved_bytes: 2 * ptr_size,
+                ..Default::default()
+            },
+            allocator.stats()
+        );
+
+        let nodes: [_; 5] = core::array::from_fn(|_| allocator.get_node());
+        unsafe {
+            allocator.return_node(nodes[0]);
+            allocator.return_node(nodes[1]);
+        }
+        allocator.try_reserve(8).unwrap();
+        assert_eq!(
+            AllocatorStats {
+                blocks: 3,
+                live_nodes: 3,
+                free_nodes: 6,
+                peak_live_nodes: 5,
+                reserved_bytes: 3 * block_size + 4 * ptr_size,
+            },
+            allocator.stats()
+        );
+    }
+
+    #[test]
+    fn block_allocator_align() {
+        let mut allocator: BlockAllocator<Node<u8>> = BlockAllocator::new(4, 1)
+            .with_block_align(4096)
+            .with_node_align(CACHE_LINE_SIZE);
+        let nodes: [_; 6] = core::array::from_fn(|_| allocator.get_node());
+        for block in allocator.blocks() {
+            assert_eq!(0, *block as usize % 4096);
+        }
+        for pair in nodes.windows(2).take(3) {
+            assert_eq!(CACHE_LINE_SIZE, pair[1] as usize - pair[0] as usize);
+        }
+        unsafe {
+            for node in nodes {
+                allocator.return_node(node);
+            }
+        }
+        allocator.try_reserve(10).unwrap();
+        assert_eq!(3, allocator.stats().blocks);
+        assert!(allocator.block_layout().unwrap().size() >= 4 * CACHE_LINE_SIZE);
+
+        let mut stack = LinkedListStack::with_allocator(
+            BlockAllocator::new(4, 1).with_node_align(CACHE_LINE_SIZE),
+        );
+        for i in 0..10 {
+            stack.push(i);
+        }
+        for i in (0..10).rev() {
+            assert_eq!(i, stack.pop());
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "node alignment set after allocating nodes")]
+    fn block_allocator_align_too_late() {
+        let mut allocator: BlockAllocator<Node<u8>> = BlockAllocator::new(4, 1);
+        allocator.get_node();
+        let _ = allocator.with_node_align(CACHE_LINE_SIZE);
+    }
+
+    #[test]
+    #[cfg(feature = "derive")]
+    fn derive_nodable() {
+        use crate::queues::IntrusiveQueue;
+
+        #[derive(Nodable)]
+        struct Task {
+            id: u32,
+            payload: MaybeUninit<[u8; 16]>,
+            #[nodable(next)]
+            next: *mut Task,
+        }
+
+        #[derive(Nodable)]
+        struct Pair<T>(T, #[nodable(next)] *mut Pair<T>);
+
+        let mut allocator: BlockAllocator<Task> = BlockAllocator::new(4, 1);
+        let tasks: [_; 3] = core::array::from_fn(|_| allocator.get_node());
+        let mut q = IntrusiveQueue::new();
+        for (id, &task) in tasks.iter().enumerate() {
+            let task = unsafe { &mut *task };
+            assert_eq!((0, ptr::null_mut()), (task.id, task.next()));
+            task.id = id as u32;
+            task.payload = MaybeUninit::new([id as u8; 16]);
+            q.enqueue(task);
+        }
+        assert_eq!(tasks[1], q.peek().next());
+        while !q.is_empty() {
+            let task: *mut Task = q.dequeue();
+            unsafe { allocator.return_node(task) };
+        }
+        assert_eq!(0, allocator.stats().live_nodes);
+
+        let mut pair = Pair::<u8>::default();
+        assert_eq!((0, ptr::null_mut()), (pair.0, pair.1));
+        let other: *mut Pair<u8> = &mut Pair(1, ptr::null_mut());
+        *pair.next_mut() = other;
+        assert_eq!(other, pair.next());
+    }
+
+    #[test]
+    fn block_allocator_chain() {
+        let mut allocator: BlockAllocator<Node<usize>> = BlockAllocator::new(4, 1);
+        assert_eq!((ptr::null_mut(), ptr::null_mut()), allocator.get_chain(0));
+
+        let node = allocator.get_node();
+        unsafe { allocator.return_node(node) };
+        let (head, tail) = allocator.get_chain(6);
+        let mut len = 0;
+        let mut current = head;
+        while !current.is_null() {
+            len += 1;
+            if current == tail {
+                assert!(unsafe { (*current).next }.is_null());
+            }
+            current = unsafe { (*current).next };
+        }
+        assert_eq!(6, len);
+        assert_eq!(6, allocator.stats().live_nodes);
+
+        unsafe { allocator.return_chain(head, tail, 6) };
+        let stats = allocator.stats();
+        assert_eq!((0, 6), (stats.live_nodes, stats.free_nodes));
+    }
+
+    #[test]
+    #[cfg(feature = "debug-alloc")]
+    #[should_panic(expected = "double return")]
+    fn block_allocator_double_return() {
+        let mut allocator: BlockAllocator<Node<usize>> = BlockAllocator::new(4, 1);
+        let node = allocator.get_node();
+        unsafe {
+            allocator.return_node(node);
+            allocator.return_node(node);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "debug-alloc")]
+    #[should_panic(expected = "foreign pointer")]
+    fn block_allocator_foreign_pointer() {
+        let mut allocator: BlockAllocator<Node<usize>> = BlockAllocator::new(4, 1);
+        let mut other: BlockAllocator<Node<usize>> = BlockAllocator::new(4, 1);
+        allocator.get_node();
+        let node = other.get_node();
+        unsafe { allocator.return_node(node) };
+    }
+
+    #[test]
+    #[cfg(feature = "debug-alloc")]
+    #[should_panic(expected = "use after return")]
+    fn block_allocator_use_after_return() {
+        let mut allocator: BlockAllocator<Node<usize>> = BlockAllocator::new(4, 1);
+        let node = allocator.get_node();
+        unsafe {
+            allocator.return_node(node);
+            (*node).val = MaybeUninit::new(42);
+        }
+        allocator.get_node();
+    }
+
+    #[test]
+    #[cfg(feature = "sync")]
+    fn sync_shared_pool() {
+        use crate::queues::LinkedListQueue;
+        let pool = SyncSharedPool::<Node<usize>>::new(16, 1);
+        let threads: [_; 4] = core::array::from_fn(|i| {
+            let pool = pool.clone();
+            std::thread::spawn(move || {
+                let mut q = LinkedListQueue::with_allocator(pool);
+                for j in 0..100 {
+                    q.enqueue(i * j);
+                }
+                (0..100).map(|_| q.dequeue()).sum::<usize>()
+            })
+        });
+        for (i, thread) in threads.into_iter().enumerate() {
+            assert_eq!(i * 4950, thread.join().unwrap());
+        }
+        let stats = pool.stats();
+        assert_eq!(0, stats.live_nodes);
+        assert!(stats.peak_live_nodes <= 400);
+    }
+}
@@ -1,8 +1,16 @@
 #![no_std]
 
+#[cfg(feature = "alloc")]
 extern crate alloc;
+#[cfg(any(test, feature = "sync"))]
+extern crate std;
+// So that derived impls, which refer to `::octads`, also work in this crate
+#[cfg(feature = "derive")]
+extern crate self as octads;
 
 pub mod allocator;
 pub mod queues;
 pub mod stacks;
 pub mod trees;
+
+mod sync;
@@ -0,0 +1,330 @@
+use core::alloc::Layout;
+use core::mem::MaybeUninit;
+use core::ptr::NonNull;
+
+use crate::allocator::{Allocator, Global};
+use crate::sync::atomic::{AtomicUsize, Ordering};
+use crate::sync::cell::UnsafeCell;
+
+/// Keeps positions updated by producers and consumers on their own cache line, see
+/// [`CACHE_LINE_SIZE`](crate::allocator::CACHE_LINE_SIZE).
+#[derive(Debug)]
+#[repr(align(64))]
+struct CachePadded<T>(T);
+
+struct Slot<T> {
+    /// Equals the position of the next enqueue into the slot while it is empty, and that
+    /// position plus one once it holds its value.
+    sequence: AtomicUsize,
+    val: UnsafeCell<MaybeUninit<T>>,
+}
+
+/// A lock-free multi-producer multi-consumer queue with the ring buffer layout of a
+/// [`BoundedQueue`](super::BoundedQueue), shared between threads through `&self`.
+///
+/// Values live in a ring buffer whose slots carry a sequence number, after Dmitry Vyukov's
+/// bounded MPMC queue. Producers and consumers claim positions with a CAS on their own
+/// counter, then the slot sequence tells whether it is ready for them, so that they never
+/// wait on each other unless the queue is full or empty.
+#[derive(Debug)]
+pub struct ConcurrentBoundedQueue<T, A = Global>
+where
+    A: Allocator,
+{
+    slots: NonNull<Slot<T>>,
+    mask: usize,
+    enqueue_pos: CachePadded<AtomicUsize>,
+    dequeue_pos: CachePadded<AtomicUsize>,
+    alloc: A,
+}
+
+unsafe impl<T, A> Send for ConcurrentBoundedQueue<T, A>
+where
+    T: Send,
+    A: Allocator + Send,
+{
+}
+
+unsafe impl<T, A> Sync for ConcurrentBoundedQueue<T, A>
+where
+    T: Send,
+    A: Allocator + Sync,
+{
+}
+
+#[cfg(feature = "alloc")]
+impl<T> ConcurrentBoundedQueue<T> {
+    pub fn new(max_size: usize) -> Self {
+        Self::new_in(max_size, Global)
+    }
+}
+
+impl<T, A> ConcurrentBoundedQueue<T, A>
+where
+    A: Allocator,
+{
+    /// `max_size` is rounded up to a power of two, and to at least 2.
+    pub fn new_in(max_size: usize, alloc: A) -> Self {
+        let max_size = max_size
+            .max(2)
+            .checked_next_power_of_two()
+            .expect("Couldn't create memory layout");
+        let layout = Layout::array::<Slot<T>>(max_size).expect("Couldn't create memory layout");
+        let slots = alloc
+            .allocate(layout)
+            .unwrap_or_else(|err| err.handle())
+            .cast::<Slot<T>>();
+        for i in 0..max_size {
+            unsafe {
+                slots.add(i).write(Slot {
+                    sequence: AtomicUsize::new(i),
+                    val: UnsafeCell::new(MaybeUninit::uninit()),
+                })
+            };
+        }
+
+        Self {
+            slots,
+            mask: max_size - 1,
+            enqueue_pos: CachePadded(AtomicUsize::new(0)),
+            dequeue_pos: CachePadded(AtomicUsize::new(0)),
+            alloc,
+        }
+    }
+
+    /// The number of values, which other threads may have changed already.
+    pub fn len(&self) -> usize {
+        let dequeue_pos = self.dequeue_pos.0.load(Ordering::Relaxed);
+        let enqueue_pos = self.enqueue_pos.0.load(Ordering::Relaxed);
+        // Positions read in this order can only be seen behind each other by a concurrent
+        // dequeue, which then made the queue empty
+        (enqueue_pos.wrapping_sub(dequeue_pos) as isize).clamp(0, self.max_len() as isize) as usize
+    }
+
+    /// Whether the queue is empty, which other threads may have changed already.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn max_len(&self) -> usize {
+        self.mask + 1
+    }
+
+    /// Gives `val` back if the queue is full.
+    pub fn try_enqueue(&self, val: T) -> Result<(), T> {
+        let mut pos = self.enqueue_pos.0.load(Ordering::Relaxed);
+        loop {
+            let slot = self.slot(pos);
+            let sequence = slot.sequence.load(Ordering::Acquire);
+            match sequence.wrapping_sub(pos) as isize {
+                // The slot is empty, claim it
+                0 => match self.enqueue_pos.0.compare_exchange_weak(
+                    pos,
+                    pos.wrapping_add(1),
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => {
+                        slot.val
+                            .with_mut(|slot_val| unsafe { (*slot_val).write(val) });
+                        slot.sequence.store(pos.wrapping_add(1), Ordering::Release);
+                        return Ok(());
+                    }
+                    Err(current) => pos = current,
+                },
+                // The slot still holds the value from the previous lap
+                diff if diff < 0 => return Err(val),
+                // Another producer claimed the position
+                _ => pos = self.enqueue_pos.0.load(Ordering::Relaxed),
+            }
+        }
+    }
+
+    /// Returns `None` if the queue is empty.
+    pub fn try_dequeue(&self) -> Option<T> {
+        let mut pos = self.dequeue_pos.0.load(Ordering::Relaxed);
+        loop {
+            let slot = self.slot(pos);
+            let sequence = slot.sequence.load(Ordering::Acquire);
+            match sequence.wrapping_sub(pos.wrapping_add(1)) as isize {
+                // The slot holds its value, claim it
+                0 => match self.dequeue_pos.0.compare_exchange_weak(
+                    pos,
+                    pos.wrapping_add(1),
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => {
+                        let val = slot
+                            .val
+                            .with_mut(|slot_val| unsafe { (*slot_val).assume_init_read() });
+                        // Ready for the enqueue of the next lap
+                        slot.sequence
+                            .store(pos.wrapping_add(self.mask + 1), Ordering::Release);
+                        return Some(val);
+                    }
+                    Err(current) => pos = current,
+                },
+                // No value was enqueued at this position yet
+                diff if diff < 0 => return None,
+                // Another consumer claimed the position
+                _ => pos = self.dequeue_pos.0.load(Ordering::Relaxed),
+            }
+        }
+    }
+
+    fn slot(&self, pos: usize) -> &Slot<T> {
+        unsafe { &*self.slots.as_ptr().add(pos & self.mask) }
+    }
+}
+
+impl<T, A> Drop for ConcurrentBoundedQueue<T, A>
+where
+    A: Allocator,
+{
+    fn drop(&mut self) {
+        while self.try_dequeue().is_some() {}
+        let max_size = self.max_len();
+        for i in 0..max_size {
+            unsafe { self.slots.as_ptr().add(i).drop_in_place() };
+        }
+        let layout = Layout::array::<Slot<T>>(max_size).unwrap();
+        unsafe { self.alloc.deallocate(self.slots.cast(), layout) };
+    }
+}
+
+impl<T> core::fmt::Debug for Slot<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Slot")
+            .field("sequence", &self.sequence)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(not(loom))]
+    fn concurrent_bounded_queue_ok() {
+        let q = ConcurrentBoundedQueue::new(3);
+        assert_eq!(4, q.max_len());
+        assert_eq!(None, q.try_dequeue());
+        for i in 0..4 {
+            assert_eq!(Ok(()), q.try_enqueue(i));
+        }
+        assert_eq!(Err(4), q.try_enqueue(4));
+        assert_eq!(4, q.len());
+
+        // Wrap around the ring a few times
+        for i in 4..10 {
+            assert_eq!(Some(i - 4), q.try_dequeue());
+            assert_eq!(Ok(()), q.try_enqueue(i));
+        }
+        for i in 6..10 {
+            assert_eq!(Some(i), q.try_dequeue());
+        }
+        assert!(q.is_empty());
+    }
+
+    #[test]
+    #[cfg(not(loom))]
+    fn concurrent_bounded_queue_drop() {
+        use std::rc::Rc;
+
+        let val = Rc::new(());
+        let q = ConcurrentBoundedQueue::new(4);
+        for _ in 0..3 {
+            q.try_enqueue(val.clone()).unwrap();
+        }
+        q.try_dequeue();
+        assert_eq!(3, Rc::strong_count(&val));
+        drop(q);
+        assert_eq!(1, Rc::strong_count(&val));
+    }
+
+    #[test]
+    #[cfg(not(loom))]
+    fn concurrent_bounded_queue_threads() {
+        use std::sync::atomic::AtomicUsize;
+
+        let q = ConcurrentBoundedQueue::new(8);
+        let sum = AtomicUsize::new(0);
+        std::thread::scope(|scope| {
+            for i in 0..4 {
+                let q = &q;
+                scope.spawn(move || {
+                    for j in 0..1000 {
+                        let mut val = i * 1000 + j;
+                        while let Err(back) = q.try_enqueue(val) {
+                            val = back;
+                            std::thread::yield_now();
+                        }
+                    }
+                });
+            }
+            for _ in 0..4 {
+                let (q, sum) = (&q, &sum);
+                scope.spawn(move || {
+                    let mut last = [None; 4];
+                    for _ in 0..1000 {
+                        let val = loop {
+                            match q.try_dequeue() {
+                                Some(val) => break val,
+                                None => std::thread::yield_now(),
+                            }
+                        };
+                        // Values of each producer are dequeued in order
+                        let producer = val / 1000;
+                        assert!(last[producer] < Some(val));
+                        last[producer] = Some(val);
+                        sum.fetch_add(val, Ordering::Relaxed);
+                    }
+                });
+            }
+        });
+        assert!(q.is_empty());
+        assert_eq!((0..4000).sum::<usize>(), sum.into_inner());
+    }
+
+    #[test]
+    #[cfg(loom)]
+    fn concurrent_bounded_queue_loom() {
+        use loom::sync::Arc;
+
+        let mut model = loom::model::Builder::new();
+        model.preemption_bound = Some(3);
+        model.check(|| {
+            let q = Arc::new(ConcurrentBoundedQueue::new(2));
+            q.try_enqueue(0).unwrap();
+
+            // A producer and a consumer racing on the same slots of a full ring
+            let producers: [_; 2] = core::array::from_fn(|i| {
+                let q = Arc::clone(&q);
+                loom::thread::spawn(move || q.try_enqueue(i + 1).is_ok())
+            });
+            let consumer = {
+                let q = Arc::clone(&q);
+                loom::thread::spawn(move || [q.try_dequeue(), q.try_dequeue()])
+            };
+            let enqueued = producers.map(|thread| thread.join().unwrap());
+            let mut dequeued = consumer.join().unwrap();
+            assert_eq!(Some(0), dequeued[0]);
+
+            let mut seen = [false; 3];
+            seen[0] = true;
+            for val in dequeued.iter_mut().skip(1).filter_map(Option::take) {
+                assert!(!seen[val], "value dequeued twice");
+                seen[val] = true;
+            }
+            while let Some(val) = q.try_dequeue() {
+                assert!(!seen[val], "value dequeued twice");
+                seen[val] = true;
+            }
+            for (i, enqueued) in enqueued.into_iter().enumerate() {
+                assert_eq!(enqueued, seen[i + 1]);
+            }
+        });
+    }
+}
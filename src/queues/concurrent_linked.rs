@@ -0,0 +1,580 @@
+use core::alloc::Layout;
+use core::cell::Cell;
+use core::marker::PhantomData;
+use core::mem::MaybeUninit;
+use core::ptr::{self, NonNull};
+
+use crate::allocator::{AllocError, Allocator, ConcurrentBlockAllocator, Global, Nodable};
+use crate::sync::atomic::{AtomicBool, AtomicPtr, AtomicUsize, Ordering};
+use crate::sync::cell::UnsafeCell;
+
+/// Hazard pointers per record, a dequeue protects both the head and its successor.
+const HAZARDS: usize = 2;
+
+/// The node of a [`ConcurrentLinkedQueue`].
+///
+/// The queue links nodes through an atomic pointer, while pools link free nodes through a
+/// separate plain one, so that a stale read of a recycled node never races with its pool.
+#[derive(Debug)]
+pub struct AtomicNode<T> {
+    next: AtomicPtr<AtomicNode<T>>,
+    link: *mut AtomicNode<T>,
+    val: UnsafeCell<MaybeUninit<T>>,
+}
+
+impl<T> Default for AtomicNode<T> {
+    fn default() -> Self {
+        Self {
+            next: AtomicPtr::new(ptr::null_mut()),
+            link: ptr::null_mut(),
+            val: UnsafeCell::new(MaybeUninit::uninit()),
+        }
+    }
+}
+
+impl<T> Nodable for AtomicNode<T> {
+    fn next(&self) -> *mut Self {
+        self.link
+    }
+
+    fn next_mut(&mut self) -> &mut *mut Self {
+        &mut self.link
+    }
+}
+
+/// Hazard pointers of one operation at a time, records are reused but never freed before
+/// the queue.
+struct Record<T> {
+    /// Written once before the record is published.
+    next: *mut Record<T>,
+    active: AtomicBool,
+    hazards: [AtomicPtr<AtomicNode<T>>; HAZARDS],
+    /// Nodes unlinked by operations that held this record, linked through their `link`.
+    /// Only accessed while the record is active, `active` hands them over.
+    retired: Cell<*mut AtomicNode<T>>,
+    retired_len: Cell<usize>,
+}
+
+/// An active [`Record`], released on drop.
+struct Guard<'a, T> {
+    record: &'a Record<T>,
+    records: &'a AtomicPtr<Record<T>>,
+    records_len: &'a AtomicUsize,
+}
+
+impl<T> Guard<'_, T> {
+    /// Loads `src` into hazard pointer `i`, once it is known to still be there afterwards.
+    fn protect(&self, i: usize, src: &AtomicPtr<AtomicNode<T>>) -> *mut AtomicNode<T> {
+        let mut node = src.load(Ordering::Acquire);
+        loop {
+            self.record.hazards[i].store(node, Ordering::SeqCst);
+            let current = src.load(Ordering::SeqCst);
+            if current == node {
+                return node;
+            }
+            node = current;
+        }
+    }
+
+    /// Returns `node` to `pool` once no hazard pointer protects it anymore.
+    ///
+    /// # Safety
+    ///
+    /// `node` must be unlinked from the queue and have its value moved out.
+    unsafe fn retire<A>(
+        &self,
+        node: *mut AtomicNode<T>,
+        pool: &ConcurrentBlockAllocator<AtomicNode<T>, A>,
+    ) where
+        A: Allocator,
+    {
+        unsafe { ptr::addr_of_mut!((*node).link).write(self.record.retired.get()) };
+        self.record.retired.set(node);
+        let retired_len = self.record.retired_len.get() + 1;
+        self.record.retired_len.set(retired_len);
+        if retired_len >= 2 * HAZARDS * self.records_len.load(Ordering::Relaxed) {
+            unsafe { self.reclaim(pool) };
+        }
+    }
+
+    /// Returns retired nodes that are not protected, in O(retired * records).
+    unsafe fn reclaim<A>(&self, pool: &ConcurrentBlockAllocator<AtomicNode<T>, A>)
+    where
+        A: Allocator,
+    {
+        let mut node = self.record.retired.replace(ptr::null_mut());
+        let mut kept = ptr::null_mut();
+        let mut kept_len = 0;
+        while !node.is_null() {
+            let next = unsafe { (*node).link };
+            if self.is_protected(node) {
+                unsafe { (*node).link = kept };
+                kept = node;
+                kept_len += 1;
+            } else {
+                unsafe {
+                    ptr::drop_in_place(node);
+                    pool.return_node(node);
+                }
+            }
+            node = next;
+        }
+        self.record.retired.set(kept);
+        self.record.retired_len.set(kept_len);
+    }
+
+    fn is_protected(&self, node: *mut AtomicNode<T>) -> bool {
+        let mut record = self.records.load(Ordering::Acquire);
+        while !record.is_null() {
+            let hazards = unsafe { &(*record).hazards };
+            if hazards
+                .iter()
+                .any(|hazard| hazard.load(Ordering::SeqCst) == node)
+            {
+                return true;
+            }
+            record = unsafe { (*record).next };
+        }
+        false
+    }
+}
+
+impl<T> Drop for Guard<'_, T> {
+    fn drop(&mut self) {
+        for hazard in &self.record.hazards {
+            hazard.store(ptr::null_mut(), Ordering::Release);
+        }
+        self.record.active.store(false, Ordering::Release);
+    }
+}
+
+/// A lock-free unbounded multi-producer multi-consumer queue, the concurrent counterpart
+/// of [`LinkedListQueue`](super::LinkedListQueue).
+///
+/// This is the Michael–Scott queue: values are linked after a dummy head node, producers
+/// CAS new nodes after the tail and consumers CAS the head forward, each helping to swing
+/// a lagging tail. Nodes come from a [`ConcurrentBlockAllocator`], and unlinked ones are
+/// only returned to it once no hazard pointer of a concurrent operation protects them.
+///
+/// Each operation in progress holds a record of hazard pointers, allocated the first time
+/// that more threads than ever before operate at once. Reserving them up front with
+/// [`try_reserve_threads`](Self::try_reserve_threads) keeps dequeues from allocating.
+#[derive(Debug)]
+pub struct ConcurrentLinkedQueue<T, A = Global>
+where
+    A: Allocator,
+{
+    allocator: ConcurrentBlockAllocator<AtomicNode<T>, A>,
+    head: AtomicPtr<AtomicNode<T>>,
+    tail: AtomicPtr<AtomicNode<T>>,
+    len: AtomicUsize,
+    records: AtomicPtr<Record<T>>,
+    records_len: AtomicUsize,
+    _marker: PhantomData<*mut T>,
+}
+
+unsafe impl<T, A> Send for ConcurrentLinkedQueue<T, A>
+where
+    T: Send,
+    A: Allocator + Send,
+{
+}
+
+unsafe impl<T, A> Sync for ConcurrentLinkedQueue<T, A>
+where
+    T: Send,
+    A: Allocator + Sync,
+{
+}
+
+#[cfg(feature = "alloc")]
+impl<T> Default for ConcurrentLinkedQueue<T> {
+    fn default() -> Self {
+        Self::new(ConcurrentBlockAllocator::<AtomicNode<T>>::DEFAULT_BLOCK_SIZE)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T> ConcurrentLinkedQueue<T> {
+    pub fn new(block_size: usize) -> Self {
+        Self::new_in(block_size, Global)
+    }
+}
+
+impl<T, A> ConcurrentLinkedQueue<T, A>
+where
+    A: Allocator,
+{
+    pub fn new_in(block_size: usize, alloc: A) -> Self {
+        Self::with_allocator(ConcurrentBlockAllocator::new_in(block_size, alloc))
+    }
+
+    /// Reserves hazard pointers for one thread, see [`reserve_threads`](Self::reserve_threads).
+    pub fn with_allocator(allocator: ConcurrentBlockAllocator<AtomicNode<T>, A>) -> Self {
+        let dummy = allocator.get_node();
+        let queue = Self {
+            allocator,
+            head: AtomicPtr::new(dummy),
+            tail: AtomicPtr::new(dummy),
+            len: AtomicUsize::new(0),
+            records: AtomicPtr::new(ptr::null_mut()),
+            records_len: AtomicUsize::new(0),
+            _marker: PhantomData,
+        };
+        queue.reserve_threads(1);
+        queue
+    }
+
+    pub fn reserve_threads(&self, threads: usize) {
+        self.try_reserve_threads(threads)
+            .unwrap_or_else(|err| err.handle())
+    }
+
+    /// Allocates hazard pointers until `threads` threads can operate on the queue at once
+    /// without allocating any more.
+    pub fn try_reserve_threads(&self, threads: usize) -> Result<(), AllocError> {
+        while self.records_len.load(Ordering::Relaxed) < threads {
+            self.add_record(false)?;
+        }
+        Ok(())
+    }
+
+    /// The allocator that nodes come from.
+    pub fn allocator(&self) -> &ConcurrentBlockAllocator<AtomicNode<T>, A> {
+        &self.allocator
+    }
+
+    /// The number of values, which other threads may have changed already.
+    pub fn len(&self) -> usize {
+        self.len.load(Ordering::Relaxed)
+    }
+
+    /// Whether the queue is empty, which other threads may have changed already.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn enqueue(&self, val: T) {
+        self.try_enqueue(val).unwrap_or_else(|err| err.handle())
+    }
+
+    pub fn try_enqueue(&self, val: T) -> Result<(), AllocError> {
+        let guard = self.guard()?;
+        let node = self.allocator.try_get_node()?;
+        unsafe { (*node).val.with_mut(|slot| (*slot).write(val)) };
+        // Counted before being linked, so that concurrent dequeues never underflow
+        self.len.fetch_add(1, Ordering::Relaxed);
+        loop {
+            let tail = guard.protect(0, &self.tail);
+            let next = unsafe { (*tail).next.load(Ordering::Acquire) };
+            if !next.is_null() {
+                // Help the producer that linked `next` to swing the tail
+                let _ = self
+                    .tail
+                    .compare_exchange(tail, next, Ordering::SeqCst, Ordering::Relaxed);
+                continue;
+            }
+            let linked = unsafe {
+                (*tail).next.compare_exchange(
+                    ptr::null_mut(),
+                    node,
+                    Ordering::Release,
+                    Ordering::Relaxed,
+                )
+            };
+            if linked.is_ok() {
+                // Someone else may have helped already
+                let _ = self
+                    .tail
+                    .compare_exchange(tail, node, Ordering::SeqCst, Ordering::Relaxed);
+                return Ok(());
+            }
+        }
+    }
+
+    /// Returns `Ok(None)` if the queue is empty, and an error if more threads than ever
+    /// before operate on the queue and allocating their hazard pointers fails.
+    pub fn try_dequeue(&self) -> Result<Option<T>, AllocError> {
+        let guard = self.guard()?;
+        loop {
+            let head = guard.protect(0, &self.head);
+            let next = guard.protect(1, unsafe { &(*head).next });
+            // `next` could already be retired if `head` was not the head anymore
+            if self.head.load(Ordering::SeqCst) != head {
+                continue;
+            }
+            if next.is_null() {
+                return Ok(None);
+            }
+            // The head never goes past the tail
+            if self.tail.load(Ordering::SeqCst) == head {
+                let _ = self
+                    .tail
+                    .compare_exchange(head, next, Ordering::SeqCst, Ordering::Relaxed);
+                continue;
+            }
+            if self
+                .head
+                .compare_exchange(head, next, Ordering::SeqCst, Ordering::Relaxed)
+                .is_ok()
+            {
+                // `next` is the new dummy, only this thread reads its value
+                let val = unsafe { (*next).val.with_mut(|slot| (*slot).assume_init_read()) };
+                self.len.fetch_sub(1, Ordering::Relaxed);
+                unsafe { guard.retire(head, &self.allocator) };
+                return Ok(Some(val));
+            }
+        }
+    }
+
+    /// Gets an inactive record, or allocates a new one.
+    fn guard(&self) -> Result<Guard<'_, T>, AllocError> {
+        match self.inactive_guard() {
+            Some(guard) => Ok(guard),
+            None => self.add_record(true).map(|record| self.guard_of(record)),
+        }
+    }
+
+    fn guard_of(&self, record: *mut Record<T>) -> Guard<'_, T> {
+        Guard {
+            record: unsafe { &*record },
+            records: &self.records,
+            records_len: &self.records_len,
+        }
+    }
+
+    /// Gets an inactive record, if any.
+    fn inactive_guard(&self) -> Option<Guard<'_, T>> {
+        let mut record = self.records.load(Ordering::Acquire);
+        while !record.is_null() {
+            let active = unsafe { &(*record).active };
+            if !active.load(Ordering::Relaxed)
+                && active
+                    .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+                    .is_ok()
+            {
+                return Some(self.guard_of(record));
+            }
+            record = unsafe { (*record).next };
+        }
+        None
+    }
+
+    /// Allocates a record and publishes it, already held if `active`.
+    fn add_record(&self, active: bool) -> Result<*mut Record<T>, AllocError> {
+        let record = self
+            .allocator
+            .allocator()
+            .allocate(Layout::new::<Record<T>>())?
+            .cast::<Record<T>>()
+            .as_ptr();
+        let mut head = self.records.load(Ordering::Relaxed);
+        unsafe {
+            record.write(Record {
+                next: head,
+                active: AtomicBool::new(active),
+                hazards: core::array::from_fn(|_| AtomicPtr::new(ptr::null_mut())),
+                retired: Cell::new(ptr::null_mut()),
+                retired_len: Cell::new(0),
+            })
+        };
+        while let Err(current) =
+            self.records
+                .compare_exchange_weak(head, record, Ordering::Release, Ordering::Relaxed)
+        {
+            head = current;
+            unsafe { (*record).next = head };
+        }
+        self.records_len.fetch_add(1, Ordering::Relaxed);
+        Ok(record)
+    }
+}
+
+impl<T, A> Drop for ConcurrentLinkedQueue<T, A>
+where
+    A: Allocator,
+{
+    fn drop(&mut self) {
+        let mut record = self.records.load(Ordering::Relaxed);
+        while !record.is_null() {
+            unsafe {
+                let mut node = (*record).retired.get();
+                while !node.is_null() {
+                    let next = (*node).link;
+                    ptr::drop_in_place(node);
+                    self.allocator.return_node(node);
+                    node = next;
+                }
+                let next = (*record).next;
+                ptr::drop_in_place(record);
+                self.allocator.allocator().deallocate(
+                    NonNull::new_unchecked(record).cast(),
+                    Layout::new::<Record<T>>(),
+                );
+                record = next;
+            }
+        }
+
+        // The dummy head has no value
+        let mut node = self.head.load(Ordering::Relaxed);
+        let mut has_val = false;
+        while !node.is_null() {
+            unsafe {
+                let next = (*node).next.load(Ordering::Relaxed);
+                if has_val {
+                    (*node).val.with_mut(|slot| (*slot).assume_init_drop());
+                }
+                ptr::drop_in_place(node);
+                self.allocator.return_node(node);
+                node = next;
+            }
+            has_val = true;
+        }
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(not(loom))]
+    fn concurrent_linked_queue_ok() {
+        let q = ConcurrentLinkedQueue::new(2);
+        assert_eq!(Ok(None), q.try_dequeue());
+        for i in 0..10 {
+            q.enqueue(i);
+        }
+        assert_eq!(10, q.len());
+        for i in 0..5 {
+            assert_eq!(Ok(Some(i)), q.try_dequeue());
+        }
+        for i in 10..15 {
+            q.enqueue(i);
+        }
+        for i in 5..15 {
+            assert_eq!(Ok(Some(i)), q.try_dequeue());
+        }
+        assert!(q.is_empty());
+        assert_eq!(Ok(None), q.try_dequeue());
+
+        // Single threaded, unlinked nodes go back to the allocator after a few dequeues
+        let stats = q.allocator().stats();
+        assert!(stats.live_nodes <= 1 + 2 * HAZARDS);
+        assert_eq!(1, q.records_len.load(Ordering::Relaxed));
+
+        q.reserve_threads(3);
+        assert_eq!(3, q.records_len.load(Ordering::Relaxed));
+        q.reserve_threads(2);
+        assert_eq!(3, q.records_len.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    #[cfg(not(loom))]
+    fn concurrent_linked_queue_unreserved() {
+        let q = ConcurrentLinkedQueue::new(4);
+        q.enqueue(1);
+        q.enqueue(2);
+        // An operation in progress holds the only record, a dequeue allocates another one
+        let guard = q.guard().unwrap();
+        assert_eq!(Ok(Some(1)), q.try_dequeue());
+        assert_eq!(2, q.records_len.load(Ordering::Relaxed));
+        drop(guard);
+        assert_eq!(Ok(Some(2)), q.try_dequeue());
+        assert_eq!(2, q.records_len.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    #[cfg(not(loom))]
+    fn concurrent_linked_queue_drop() {
+        use std::rc::Rc;
+
+        let val = Rc::new(());
+        let q = ConcurrentLinkedQueue::new(4);
+        for _ in 0..3 {
+            q.enqueue(val.clone());
+        }
+        q.try_dequeue().unwrap();
+        assert_eq!(3, Rc::strong_count(&val));
+        drop(q);
+        assert_eq!(1, Rc::strong_count(&val));
+    }
+
+    #[test]
+    #[cfg(not(loom))]
+    fn concurrent_linked_queue_threads() {
+        // More consumers than reserved hazard pointers
+        let q = ConcurrentLinkedQueue::new(16);
+        let sum = AtomicUsize::new(0);
+        std::thread::scope(|scope| {
+            for i in 0..4 {
+                let q = &q;
+                scope.spawn(move || {
+                    for j in 0..1000 {
+                        q.enqueue(i * 1000 + j);
+                    }
+                });
+            }
+            for _ in 0..4 {
+                let (q, sum) = (&q, &sum);
+                scope.spawn(move || {
+                    let mut last = [None; 4];
+                    for _ in 0..1000 {
+                        let val = loop {
+                            match q.try_dequeue().unwrap() {
+                                Some(val) => break val,
+                                None => std::thread::yield_now(),
+                            }
+                        };
+                        // Values of each producer are dequeued in order
+                        let producer = val / 1000;
+                        assert!(last[producer] < Some(val));
+                        last[producer] = Some(val);
+                        sum.fetch_add(val, Ordering::Relaxed);
+                    }
+                });
+            }
+        });
+        assert!(q.is_empty());
+        assert_eq!((0..4000).sum::<usize>(), sum.into_inner());
+        assert!(q.records_len.load(Ordering::Relaxed) <= 8);
+    }
+
+    #[test]
+    #[cfg(loom)]
+    fn concurrent_linked_queue_loom() {
+        use loom::sync::Arc;
+
+        let mut model = loom::model::Builder::new();
+        model.preemption_bound = Some(2);
+        model.check(|| {
+            let q = Arc::new(ConcurrentLinkedQueue::new(2));
+            q.enqueue(0);
+
+            // Two consumers racing for the head while a producer moves the tail
+            let producer = {
+                let q = Arc::clone(&q);
+                loom::thread::spawn(move || q.enqueue(1))
+            };
+            let consumers: [_; 2] = core::array::from_fn(|_| {
+                let q = Arc::clone(&q);
+                loom::thread::spawn(move || q.try_dequeue().unwrap())
+            });
+            producer.join().unwrap();
+            let mut seen = [false; 2];
+            for consumer in consumers {
+                if let Some(val) = consumer.join().unwrap() {
+                    assert!(!seen[val], "value dequeued twice");
+                    seen[val] = true;
+                }
+            }
+            // The first value is always there for some consumer
+            assert!(seen[0]);
+            while let Some(val) = q.try_dequeue().unwrap() {
+                assert!(!seen[val], "value dequeued twice");
+                seen[val] = true;
+            }
+            assert_eq!([true, true], seen);
+        });
+    }
+}
@@ -0,0 +1,3092 @@
+use core::alloc::Layout;
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use core::iter::FusedIterator;
+use core::marker::PhantomData;
+use core::mem::{self, MaybeUninit};
+use core::ptr::{self, NonNull};
+#[cfg(target_has_atomic = "64")]
+use core::sync::atomic::{AtomicU64, Ordering};
+
+#[cfg(target_has_atomic = "ptr")]
+mod concurrent;
+#[cfg(all(feature = "hazard-pointers", target_has_atomic = "64"))]
+mod concurrent_linked;
+
+#[cfg(target_has_atomic = "ptr")]
+pub use concurrent::ConcurrentBoundedQueue;
+#[cfg(all(feature = "hazard-pointers", target_has_atomic = "64"))]
+pub use concurrent_linked::{AtomicNode, ConcurrentLinkedQueue};
+
+#[cfg(feature = "alloc")]
+use crate::allocator::SharedPool;
+use crate::allocator::{
+    AllocError, Allocator, AllocatorStats, BiNode, BlockAllocator, Global, Nodable, Node, NodePool,
+    OwningPool,
+};
+use crate::stacks::{
+    AggregateError, AggregateStack, BoundedStack, LinkedListStack, Max, Min, Monoid,
+    ShadowCopyStack, Stack,
+};
+
+/// Operations shared by all queues, so that generic code can switch implementations.
+pub trait Queue<T> {
+    /// Why [`try_enqueue`](Queue::try_enqueue) failed: the value itself for bounded
+    /// queues, an [`AllocError`] for the others.
+    type Error;
+
+    fn is_empty(&self) -> bool;
+
+    fn len(&self) -> usize;
+
+    fn enqueue(&mut self, val: T);
+
+    fn try_enqueue(&mut self, val: T) -> Result<(), Self::Error>;
+
+    fn dequeue(&mut self) -> T;
+
+    fn try_dequeue(&mut self) -> Option<T> {
+        (!self.is_empty()).then(|| self.dequeue())
+    }
+
+    fn peek(&self) -> &T;
+
+    fn try_peek(&self) -> Option<&T> {
+        (!self.is_empty()).then(|| self.peek())
+    }
+
+    fn peek_mut(&mut self) -> &mut T;
+
+    fn try_peek_mut(&mut self) -> Option<&mut T> {
+        (!self.is_empty()).then(|| self.peek_mut())
+    }
+}
+
+#[derive(Debug)]
+pub struct BoundedQueue<T, A = Global>
+where
+    A: Allocator,
+{
+    base: *mut T,
+    front: usize,
+    rear: usize,
+    max_size: usize,
+    len: usize,
+    alloc: A,
+}
+
+#[cfg(feature = "alloc")]
+impl<T> BoundedQueue<T> {
+    pub fn new(max_size: usize) -> Self {
+        Self::new_in(max_size, Global)
+    }
+}
+
+impl<T, A> BoundedQueue<T, A>
+where
+    A: Allocator,
+{
+    pub fn new_in(max_size: usize, alloc: A) -> Self {
+        Self::try_new_in(max_size, alloc).unwrap_or_else(|err| err.handle())
+    }
+
+    pub fn try_new_in(max_size: usize, alloc: A) -> Result<Self, AllocError> {
+        let layout = Layout::array::<T>(max_size).map_err(|_| AllocError::CapacityOverflow)?;
+        let base = alloc.allocate(layout)?.as_ptr() as *mut _;
+
+        Ok(Self {
+            base,
+            front: 0,
+            rear: 0,
+            max_size,
+            len: 0,
+            alloc,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn max_len(&self) -> usize {
+        self.max_size
+    }
+
+    pub fn enqueue(&mut self, val: T) {
+        assert!(
+            self.len < self.max_size,
+            "overflow: enqueuing to a full queue"
+        );
+        unsafe { ptr::write(self.base.add(self.rear), val) };
+        self.rear = (self.rear + 1) % self.max_size;
+        self.len += 1;
+    }
+
+    /// Gives `val` back if the queue is full.
+    pub fn try_enqueue(&mut self, val: T) -> Result<(), T> {
+        if self.len == self.max_size {
+            return Err(val);
+        }
+        self.enqueue(val);
+        Ok(())
+    }
+
+    pub fn dequeue(&mut self) -> T {
+        assert!(!self.is_empty(), "underflow: dequeuing from an empty queue");
+        let tmp = self.front;
+        self.front = (self.front + 1) % self.max_size;
+        self.len -= 1;
+        unsafe { ptr::read(self.base.add(tmp)) }
+    }
+
+    pub fn peek(&self) -> &T {
+        assert!(!self.is_empty(), "underflow: peeking at an empty queue");
+        unsafe {
+            let peek = self.base.add(self.front);
+            &*peek
+        }
+    }
+
+    pub fn peek_mut(&mut self) -> &mut T {
+        assert!(!self.is_empty(), "underflow: peeking at an empty queue");
+        unsafe {
+            let peek = self.base.add(self.front);
+            &mut *peek
+        }
+    }
+
+    pub fn try_dequeue(&mut self) -> Option<T> {
+        (!self.is_empty()).then(|| self.dequeue())
+    }
+
+    pub fn try_peek(&self) -> Option<&T> {
+        (!self.is_empty()).then(|| self.peek())
+    }
+
+    pub fn try_peek_mut(&mut self) -> Option<&mut T> {
+        (!self.is_empty()).then(|| self.peek_mut())
+    }
+}
+
+impl<T, A> Drop for BoundedQueue<T, A>
+where
+    A: Allocator,
+{
+    fn drop(&mut self) {
+        while !self.is_empty() {
+            self.dequeue();
+        }
+        let layout = Layout::array::<T>(self.max_size).unwrap();
+        unsafe {
+            self.alloc
+                .deallocate(NonNull::new_unchecked(self.base as *mut u8), layout)
+        };
+    }
+}
+
+/// A ring buffer queue that doubles its capacity when full.
+///
+/// Growing unwraps the ring into the new buffer, which costs O(n) once in a while, see
+/// [`ShadowCopyQueue`] for a queue without such spikes.
+#[derive(Debug)]
+pub struct RingQueue<T, A = Global>
+where
+    A: Allocator,
+{
+    base: *mut T,
+    front: usize,
+    max_size: usize,
+    len: usize,
+    alloc: A,
+}
+
+#[cfg(feature = "alloc")]
+impl<T> RingQueue<T> {
+    pub fn new(max_size: usize) -> Self {
+        Self::new_in(max_size, Global)
+    }
+}
+
+impl<T, A> RingQueue<T, A>
+where
+    A: Allocator,
+{
+    pub fn new_in(max_size: usize, alloc: A) -> Self {
+        Self::try_new_in(max_size, alloc).unwrap_or_else(|err| err.handle())
+    }
+
+    pub fn try_new_in(max_size: usize, alloc: A) -> Result<Self, AllocError> {
+        assert!(max_size > 0, "invalid capacity of 0");
+        let layout = Layout::array::<T>(max_size).map_err(|_| AllocError::CapacityOverflow)?;
+        let base = alloc.allocate(layout)?.as_ptr() as *mut _;
+
+        Ok(Self {
+            base,
+            front: 0,
+            max_size,
+            len: 0,
+            alloc,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.max_size
+    }
+
+    pub fn enqueue(&mut self, val: T) {
+        self.try_enqueue(val).unwrap_or_else(|err| err.handle())
+    }
+
+    pub fn try_enqueue(&mut self, val: T) -> Result<(), AllocError> {
+        if self.len == self.max_size {
+            self.grow()?;
+        }
+        let rear = (self.front + self.len) % self.max_size;
+        unsafe { ptr::write(self.base.add(rear), val) };
+        self.len += 1;
+        Ok(())
+    }
+
+    pub fn dequeue(&mut self) -> T {
+        assert!(!self.is_empty(), "underflow: dequeuing from an empty queue");
+        let tmp = self.front;
+        self.front = (self.front + 1) % self.max_size;
+        self.len -= 1;
+        unsafe { ptr::read(self.base.add(tmp)) }
+    }
+
+    pub fn peek(&self) -> &T {
+        assert!(!self.is_empty(), "underflow: peeking at an empty queue");
+        unsafe { &*self.base.add(self.front) }
+    }
+
+    pub fn peek_mut(&mut self) -> &mut T {
+        assert!(!self.is_empty(), "underflow: peeking at an empty queue");
+        unsafe { &mut *self.base.add(self.front) }
+    }
+
+    pub fn try_dequeue(&mut self) -> Option<T> {
+        (!self.is_empty()).then(|| self.dequeue())
+    }
+
+    pub fn try_peek(&self) -> Option<&T> {
+        (!self.is_empty()).then(|| self.peek())
+    }
+
+    pub fn try_peek_mut(&mut self) -> Option<&mut T> {
+        (!self.is_empty()).then(|| self.peek_mut())
+    }
+
+    /// Doubles the capacity, moving the values to the start of the new buffer.
+    fn grow(&mut self) -> Result<(), AllocError> {
+        let new_size = self
+            .max_size
+            .checked_mul(2)
+            .ok_or(AllocError::CapacityOverflow)?;
+        let new_layout = Layout::array::<T>(new_size).map_err(|_| AllocError::CapacityOverflow)?;
+        let new_base = self.alloc.allocate(new_layout)?.as_ptr() as *mut T;
+
+        let head_len = self.len.min(self.max_size - self.front);
+        unsafe {
+            self.base
+                .add(self.front)
+                .copy_to_nonoverlapping(new_base, head_len);
+            self.base
+                .copy_to_nonoverlapping(new_base.add(head_len), self.len - head_len);
+            let old_layout = Layout::array::<T>(self.max_size).unwrap();
+            self.alloc
+                .deallocate(NonNull::new_unchecked(self.base as *mut u8), old_layout);
+        }
+        self.base = new_base;
+        self.front = 0;
+        self.max_size = new_size;
+        Ok(())
+    }
+}
+
+impl<T, A> Drop for RingQueue<T, A>
+where
+    A: Allocator,
+{
+    fn drop(&mut self) {
+        while !self.is_empty() {
+            self.dequeue();
+        }
+        let layout = Layout::array::<T>(self.max_size).unwrap();
+        unsafe {
+            self.alloc
+                .deallocate(NonNull::new_unchecked(self.base as *mut u8), layout)
+        };
+    }
+}
+
+/// A ring buffer queue that grows like [`ShadowCopyStack`].
+///
+/// Once 3/4 full, a buffer twice as large is allocated and values are copied to it a few
+/// at a time on each operation, so the copy is complete before the ring gets full.
+#[derive(Debug)]
+pub struct ShadowCopyQueue<T, A = Global>
+where
+    A: Allocator,
+{
+    base: *mut T,
+    front: usize,
+    max_size: usize,
+    len: usize,
+    copy: *mut T,
+    /// Position in the copy of the front value.
+    copy_front: usize,
+    /// Values before this position in the copy are copied.
+    copy_end: usize,
+    alloc: A,
+}
+
+#[cfg(feature = "alloc")]
+impl<T> ShadowCopyQueue<T> {
+    pub fn new(max_size: usize) -> Self {
+        Self::new_in(max_size, Global)
+    }
+}
+
+impl<T, A> ShadowCopyQueue<T, A>
+where
+    A: Allocator,
+{
+    pub fn new_in(max_size: usize, alloc: A) -> Self {
+        Self::try_new_in(max_size, alloc).unwrap_or_else(|err| err.handle())
+    }
+
+    pub fn try_new_in(max_size: usize, alloc: A) -> Result<Self, AllocError> {
+        assert!(max_size > 0, "invalid capacity of 0");
+        let layout = Layout::array::<T>(max_size).map_err(|_| AllocError::CapacityOverflow)?;
+        let base = alloc.allocate(layout)?.as_ptr() as *mut _;
+
+        Ok(Self {
+            base,
+            front: 0,
+            max_size,
+            len: 0,
+            copy: ptr::null_mut(),
+            copy_front: 0,
+            copy_end: 0,
+            alloc,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.max_size
+    }
+
+    pub fn enqueue(&mut self, val: T) {
+        self.try_enqueue(val).unwrap_or_else(|err| err.handle())
+    }
+
+    pub fn try_enqueue(&mut self, val: T) -> Result<(), AllocError> {
+        // Integer math, a rounded 3/4 could leave the copy no time to complete
+        if self.copy.is_null() && self.len + 1 >= self.max_size - self.max_size / 4 {
+            let copy_max_size = self
+                .max_size
+                .checked_mul(2)
+                .ok_or(AllocError::CapacityOverflow)?;
+            let copy_layout =
+                Layout::array::<T>(copy_max_size).map_err(|_| AllocError::CapacityOverflow)?;
+            self.copy = self.alloc.allocate(copy_layout)?.as_ptr() as *mut _;
+        }
+        let rear = (self.front + self.len) % self.max_size;
+        unsafe { ptr::write(self.base.add(rear), val) };
+        self.len += 1;
+        if !self.copy.is_null() {
+            let mut additional_copies = 4;
+            while additional_copies > 0 && self.copy_end < self.copy_front + self.len {
+                let i = self.copy_end - self.copy_front;
+                unsafe {
+                    self.base
+                        .add((self.front + i) % self.max_size)
+                        .copy_to(self.copy.add(self.copy_end), 1)
+                };
+                self.copy_end += 1;
+                additional_copies -= 1;
+            }
+            // Copy complete
+            if self.copy_end == self.copy_front + self.len {
+                self.swap_copy();
+            }
+        }
+        Ok(())
+    }
+
+    pub fn dequeue(&mut self) -> T {
+        assert!(!self.is_empty(), "underflow: dequeuing from an empty queue");
+        let val = unsafe { ptr::read(self.slot(0)) };
+        self.front = (self.front + 1) % self.max_size;
+        self.len -= 1;
+        if !self.copy.is_null() {
+            // Values dequeued before being copied are skipped
+            self.copy_front += 1;
+            self.copy_end = self.copy_end.max(self.copy_front);
+            // Copy complete
+            if self.copy_end == self.copy_front + self.len {
+                self.swap_copy();
+            }
+        }
+        val
+    }
+
+    pub fn peek(&self) -> &T {
+        assert!(!self.is_empty(), "underflow: peeking at an empty queue");
+        unsafe { &*self.slot(0) }
+    }
+
+    pub fn peek_mut(&mut self) -> &mut T {
+        assert!(!self.is_empty(), "underflow: peeking at an empty queue");
+        unsafe { &mut *self.slot(0) }
+    }
+
+    pub fn try_dequeue(&mut self) -> Option<T> {
+        (!self.is_empty()).then(|| self.dequeue())
+    }
+
+    pub fn try_peek(&self) -> Option<&T> {
+        (!self.is_empty()).then(|| self.peek())
+    }
+
+    pub fn try_peek_mut(&mut self) -> Option<&mut T> {
+        (!self.is_empty()).then(|| self.peek_mut())
+    }
+
+    /// Where the `i`-th value lives: values already copied live in the copy, which is the
+    /// one kept once it completes, so that they can be mutated in place.
+    fn slot(&self, i: usize) -> *mut T {
+        if !self.copy.is_null() && self.copy_front + i < self.copy_end {
+            unsafe { self.copy.add(self.copy_front + i) }
+        } else {
+            unsafe { self.base.add((self.front + i) % self.max_size) }
+        }
+    }
+
+    fn swap_copy(&mut self) {
+        let base_layout = Layout::array::<T>(self.max_size).unwrap();
+        unsafe {
+            self.alloc
+                .deallocate(NonNull::new_unchecked(self.base as *mut u8), base_layout)
+        };
+        self.base = self.copy;
+        self.front = self.copy_front;
+        self.max_size *= 2;
+        self.copy = ptr::null_mut();
+        self.copy_front = 0;
+        self.copy_end = 0;
+    }
+}
+
+impl<T, A> Drop for ShadowCopyQueue<T, A>
+where
+    A: Allocator,
+{
+    fn drop(&mut self) {
+        for i in 0..self.len {
+            unsafe { ptr::drop_in_place(self.slot(i)) };
+        }
+        let base_layout = Layout::array::<T>(self.max_size).unwrap();
+        unsafe {
+            self.alloc
+                .deallocate(NonNull::new_unchecked(self.base as *mut u8), base_layout)
+        };
+        if !self.copy.is_null() {
+            let copy_layout = Layout::array::<T>(2 * self.max_size).unwrap();
+            unsafe {
+                self.alloc
+                    .deallocate(NonNull::new_unchecked(self.copy as *mut u8), copy_layout)
+            };
+        }
+    }
+}
+
+/// A queue built from stacks, whose operations all take worst-case O(1) time, after
+/// Hood and Melville.
+///
+/// Values are dequeued from a front [`BoundedStack`] and enqueued on a rear
+/// [`ShadowCopyStack`]. Once the rear gets larger than the front, the next front is built
+/// from the rear reversed, below a copy of the front, while the current front keeps serving
+/// dequeues. Each operation then moves at most 3 values, which is enough to complete the
+/// next front before the current one runs out.
+///
+/// Per operation in the worst case, enqueuing pushes on the rear and dequeuing pops from
+/// the front, both in O(1). Only enqueuing allocates, at most one uninitialized array
+/// large enough to hold the next front, so that dequeuing never fails.
+#[derive(Debug)]
+pub struct RealTimeQueue<T, A = Global>
+where
+    A: Allocator + Clone,
+{
+    front: BoundedStack<T, A>,
+    rear: ShadowCopyStack<T, A>,
+    /// The front being built, only while rotating.
+    next_front: Option<BoundedStack<T, A>>,
+    /// The rear being reversed into `next_front`, new values go to `rear` meanwhile.
+    old_rear: ShadowCopyStack<T, A>,
+    /// Values at the bottom of `front` already copied to `next_front`.
+    copied: usize,
+    /// The next front, allocated ahead by enqueues. It can hold the whole queue whenever
+    /// no rotation is running and the rear holds values, i.e. whenever a dequeue may start
+    /// a rotation.
+    spare: Option<BoundedStack<T, A>>,
+    len: usize,
+    alloc: A,
+}
+
+#[cfg(feature = "alloc")]
+impl<T> RealTimeQueue<T> {
+    pub fn new(base_size: usize) -> Self {
+        Self::new_in(base_size, Global)
+    }
+}
+
+impl<T, A> RealTimeQueue<T, A>
+where
+    A: Allocator + Clone,
+{
+    /// Values moved towards the next front on each operation.
+    const ROTATION_STEPS: usize = 3;
+
+    /// `base_size` is the initial capacity of the rear stacks, see
+    /// [`ShadowCopyStack::new`].
+    pub fn new_in(base_size: usize, alloc: A) -> Self {
+        Self::try_new_in(base_size, alloc).unwrap_or_else(|err| err.handle())
+    }
+
+    pub fn try_new_in(base_size: usize, alloc: A) -> Result<Self, AllocError> {
+        assert!(base_size > 0, "invalid capacity of 0");
+        Ok(Self {
+            front: BoundedStack::try_new_in(0, alloc.clone())?,
+            rear: ShadowCopyStack::try_new_in(base_size, alloc.clone())?,
+            next_front: None,
+            old_rear: ShadowCopyStack::try_new_in(base_size, alloc.clone())?,
+            copied: 0,
+            spare: None,
+            len: 0,
+            alloc,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn enqueue(&mut self, val: T) {
+        self.try_enqueue(val).unwrap_or_else(|err| err.handle())
+    }
+
+    pub fn try_enqueue(&mut self, val: T) -> Result<(), AllocError> {
+        // The next front is allocated first, twice as large so that it is rarely replaced
+        let len = self.len + 1;
+        if self
+            .spare
+            .as_ref()
+            .is_none_or(|spare| spare.max_len() < len)
+        {
+            let max_size = len.checked_mul(2).ok_or(AllocError::CapacityOverflow)?;
+            self.spare = Some(BoundedStack::try_new_in(max_size, self.alloc.clone())?);
+        }
+        self.rear.try_push(val)?;
+        self.len += 1;
+        self.rotate_if_needed();
+        Ok(())
+    }
+
+    pub fn dequeue(&mut self) -> T {
+        assert!(!self.is_empty(), "underflow: dequeuing from an empty queue");
+        let val = self.front.pop();
+        self.len -= 1;
+        self.rotate_if_needed();
+        val
+    }
+
+    pub fn peek(&self) -> &T {
+        assert!(!self.is_empty(), "underflow: peeking at an empty queue");
+        self.front.peek()
+    }
+
+    pub fn peek_mut(&mut self) -> &mut T {
+        assert!(!self.is_empty(), "underflow: peeking at an empty queue");
+        self.front.peek_mut()
+    }
+
+    pub fn try_dequeue(&mut self) -> Option<T> {
+        (!self.is_empty()).then(|| self.dequeue())
+    }
+
+    pub fn try_peek(&self) -> Option<&T> {
+        (!self.is_empty()).then(|| self.peek())
+    }
+
+    pub fn try_peek_mut(&mut self) -> Option<&mut T> {
+        (!self.is_empty()).then(|| self.peek_mut())
+    }
+
+    /// Starts reversing the rear into the spare once it gets larger than the front, then
+    /// moves a few values.
+    fn rotate_if_needed(&mut self) {
+        if self.next_front.is_none() && self.rear.len() > self.front.len() {
+            let next_front = self.spare.take().expect("the spare front was allocated");
+            debug_assert!(next_front.max_len() >= self.len);
+            mem::swap(&mut self.rear, &mut self.old_rear);
+            self.next_front = Some(next_front);
+            self.copied = 0;
+        }
+        self.rotate();
+    }
+
+    /// Moves a few values to the next front, and makes it the front once complete.
+    ///
+    /// The rear is reversed first, then the front is copied from its bottom while its top
+    /// is being dequeued, values dequeued meanwhile are never copied.
+    fn rotate(&mut self) {
+        let Some(next_front) = &mut self.next_front else {
+            return;
+        };
+        for _ in 0..Self::ROTATION_STEPS {
+            if !self.old_rear.is_empty() {
+                // Shrinking the old rear would allocate
+                next_front.push(self.old_rear.pop_no_shrink());
+            } else if self.copied < self.front.len() {
+                let val = unsafe { self.front.as_ptr().add(self.copied).read() };
+                next_front.push(val);
+                self.copied += 1;
+            }
+            if self.old_rear.is_empty() && self.copied == self.front.len() {
+                let mut front = mem::replace(&mut self.front, self.next_front.take().unwrap());
+                // Every value left was copied to the new front
+                unsafe { front.set_len(0) };
+                self.copied = 0;
+                // The rear is empty unless an enqueue already allocated a spare
+                if self.spare.is_none() {
+                    self.spare = Some(front);
+                }
+                return;
+            }
+        }
+        debug_assert!(!self.front.is_empty(), "rotation did not keep up");
+    }
+}
+
+impl<T, A> Drop for RealTimeQueue<T, A>
+where
+    A: Allocator + Clone,
+{
+    fn drop(&mut self) {
+        // Values copied from the front are owned by the front
+        if let Some(next_front) = &mut self.next_front {
+            unsafe { next_front.set_len(next_front.len() - self.copied) };
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct LinkedListQueue<T, P = BlockAllocator<Node<T>>>
+where
+    P: NodePool<Node<T>>,
+{
+    allocator: P,
+    len: usize,
+    remove: *mut Node<T>,
+    insert: *mut Node<T>,
+}
+
+#[cfg(feature = "alloc")]
+impl<T> Default for LinkedListQueue<T> {
+    fn default() -> Self {
+        Self::new(
+            BlockAllocator::<Node<T>>::DEFAULT_BLOCK_SIZE,
+            BlockAllocator::<Node<T>>::DEFAULT_BLOCK_CAP,
+        )
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T> LinkedListQueue<T> {
+    pub fn new(block_size: usize, blocks_cap: usize) -> Self {
+        Self::new_in(block_size, blocks_cap, Global)
+    }
+}
+
+impl<T, A> LinkedListQueue<T, BlockAllocator<Node<T>, A>>
+where
+    A: Allocator,
+{
+    pub fn new_in(block_size: usize, blocks_cap: usize, alloc: A) -> Self {
+        Self::with_allocator(BlockAllocator::new_in(block_size, blocks_cap, alloc))
+    }
+}
+
+impl<T, P> LinkedListQueue<T, P>
+where
+    P: NodePool<Node<T>>,
+{
+    pub fn with_allocator(allocator: P) -> Self {
+        Self {
+            allocator,
+            len: 0,
+            remove: ptr::null_mut(),
+            insert: ptr::null_mut(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn enqueue(&mut self, val: T) {
+        self.try_enqueue(val).unwrap_or_else(|err| err.handle())
+    }
+
+    pub fn try_enqueue(&mut self, val: T) -> Result<(), AllocError> {
+        let tmp = self.allocator.try_get_node()?;
+        unsafe { (*tmp).val = MaybeUninit::new(val) };
+        if !self.is_empty() {
+            unsafe { (*self.insert).next = tmp };
+            self.insert = tmp;
+        } else {
+            self.remove = tmp;
+            self.insert = tmp;
+        }
+        self.len += 1;
+        Ok(())
+    }
+
+    pub fn try_reserve(&mut self, additional: usize) -> Result<(), AllocError> {
+        self.allocator.try_reserve(additional)
+    }
+
+    pub fn shrink_to_fit(&mut self) {
+        self.allocator.shrink_to_fit();
+    }
+
+    pub fn allocator_stats(&self) -> AllocatorStats {
+        self.allocator.stats()
+    }
+
+    pub fn dequeue(&mut self) -> T {
+        assert!(!self.is_empty(), "underflow: dequeuing from an empty queue");
+        let tmp = self.remove;
+        unsafe {
+            self.remove = (*tmp).next;
+            let val = (*tmp).val.assume_init_read();
+            self.allocator.return_node(tmp);
+            self.len -= 1;
+            val
+        }
+    }
+
+    pub fn peek(&self) -> &T {
+        assert!(!self.is_empty(), "underflow: peeking at an empty queue");
+        unsafe { (*self.remove).val.assume_init_ref() }
+    }
+
+    pub fn peek_mut(&mut self) -> &mut T {
+        assert!(!self.is_empty(), "underflow: peeking at an empty queue");
+        unsafe { (*self.remove).val.assume_init_mut() }
+    }
+
+    pub fn try_dequeue(&mut self) -> Option<T> {
+        (!self.is_empty()).then(|| self.dequeue())
+    }
+
+    pub fn try_peek(&self) -> Option<&T> {
+        (!self.is_empty()).then(|| self.peek())
+    }
+
+    pub fn try_peek_mut(&mut self) -> Option<&mut T> {
+        (!self.is_empty()).then(|| self.peek_mut())
+    }
+}
+
+impl<T, P> Drop for LinkedListQueue<T, P>
+where
+    P: NodePool<Node<T>>,
+{
+    fn drop(&mut self) {
+        let mut next = self.remove;
+        for _ in 0..self.len {
+            unsafe {
+                (*next).val.assume_init_drop();
+                next = (*next).next;
+            }
+        }
+        unsafe {
+            self.allocator
+                .return_chain(self.remove, self.insert, self.len)
+        };
+    }
+}
+
+/// A queue linking nodes owned by the caller through [`Nodable::next_mut`], without
+/// allocating.
+///
+/// Nodes are exclusively borrowed while they are in the queue and handed back by
+/// [`dequeue`](Self::dequeue) with their link cleared.
+#[derive(Debug)]
+pub struct IntrusiveQueue<'a, N>
+where
+    N: Nodable,
+{
+    len: usize,
+    remove: *mut N,
+    insert: *mut N,
+    nodes: PhantomData<&'a mut N>,
+}
+
+impl<N> Default for IntrusiveQueue<'_, N>
+where
+    N: Nodable,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, N> IntrusiveQueue<'a, N>
+where
+    N: Nodable,
+{
+    pub fn new() -> Self {
+        Self {
+            len: 0,
+            remove: ptr::null_mut(),
+            insert: ptr::null_mut(),
+            nodes: PhantomData,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn enqueue(&mut self, node: &'a mut N) {
+        *node.next_mut() = ptr::null_mut();
+        let node: *mut N = node;
+        if !self.is_empty() {
+            unsafe { *(*self.insert).next_mut() = node };
+        } else {
+            self.remove = node;
+        }
+        self.insert = node;
+        self.len += 1;
+    }
+
+    pub fn dequeue(&mut self) -> &'a mut N {
+        assert!(!self.is_empty(), "underflow: dequeuing from an empty queue");
+        let node = unsafe { &mut *self.remove };
+        self.remove = mem::replace(node.next_mut(), ptr::null_mut());
+        self.len -= 1;
+        node
+    }
+
+    pub fn peek(&self) -> &N {
+        assert!(!self.is_empty(), "underflow: peeking at an empty queue");
+        unsafe { &*self.remove }
+    }
+
+    pub fn try_dequeue(&mut self) -> Option<&'a mut N> {
+        (!self.is_empty()).then(|| self.dequeue())
+    }
+
+    pub fn try_peek(&self) -> Option<&N> {
+        (!self.is_empty()).then(|| self.peek())
+    }
+}
+
+#[derive(Debug)]
+pub struct CircularLinkedQueue<T, P = BlockAllocator<Node<T>>>
+where
+    P: NodePool<Node<T>>,
+{
+    allocator: P,
+    len: usize,
+    entry: *mut Node<T>,
+}
+
+#[cfg(feature = "alloc")]
+impl<T> Default for CircularLinkedQueue<T> {
+    fn default() -> Self {
+        Self::new(
+            BlockAllocator::<Node<T>>::DEFAULT_BLOCK_SIZE,
+            BlockAllocator::<Node<T>>::DEFAULT_BLOCK_CAP,
+        )
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T> CircularLinkedQueue<T> {
+    pub fn new(block_size: usize, blocks_cap: usize) -> Self {
+        Self::new_in(block_size, blocks_cap, Global)
+    }
+}
+
+impl<T, A> CircularLinkedQueue<T, BlockAllocator<Node<T>, A>>
+where
+    A: Allocator,
+{
+    pub fn new_in(block_size: usize, blocks_cap: usize, alloc: A) -> Self {
+        Self::with_allocator(BlockAllocator::new_in(block_size, blocks_cap, alloc))
+    }
+}
+
+impl<T, P> CircularLinkedQueue<T, P>
+where
+    P: NodePool<Node<T>>,
+{
+    pub fn with_allocator(mut allocator: P) -> Self {
+        let entry: *mut Node<_> = allocator.get_node();
+        unsafe { (*entry).next = entry };
+        Self {
+            allocator,
+            len: 0,
+            entry,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        unsafe { self.entry == (*self.entry).next }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn enqueue(&mut self, val: T) {
+        self.try_enqueue(val).unwrap_or_else(|err| err.handle())
+    }
+
+    pub fn try_enqueue(&mut self, val: T) -> Result<(), AllocError> {
+        let node = self.allocator.try_get_node()?;
+        unsafe {
+            (*node).val = MaybeUninit::new(val);
+            let tmp = self.entry;
+            self.entry = node;
+            (*node).next = (*tmp).next;
+            (*tmp).next = node;
+        }
+        self.len += 1;
+        Ok(())
+    }
+
+    pub fn try_reserve(&mut self, additional: usize) -> Result<(), AllocError> {
+        self.allocator.try_reserve(additional)
+    }
+
+    pub fn shrink_to_fit(&mut self) {
+        self.allocator.shrink_to_fit();
+    }
+
+    pub fn allocator_stats(&self) -> AllocatorStats {
+        self.allocator.stats()
+    }
+
+    pub fn dequeue(&mut self) -> T {
+        assert!(!self.is_empty(), "underflow: dequeuing from an empty queue");
+        unsafe {
+            let tmp = (*(*self.entry).next).next;
+            (*(*self.entry).next).next = (*tmp).next;
+            if tmp == self.entry {
+                self.entry = (*tmp).next;
+            }
+            let val = (*tmp).val.assume_init_read();
+            self.allocator.return_node(tmp);
+            self.len -= 1;
+            val
+        }
+    }
+
+    pub fn peek(&self) -> &T {
+        assert!(!self.is_empty(), "underflow: peeking at an empty queue");
+        unsafe { (*(*(*self.entry).next).next).val.assume_init_ref() }
+    }
+
+    pub fn peek_mut(&mut self) -> &mut T {
+        assert!(!self.is_empty(), "underflow: peeking at an empty queue");
+        unsafe { (*(*(*self.entry).next).next).val.assume_init_mut() }
+    }
+
+    pub fn try_dequeue(&mut self) -> Option<T> {
+        (!self.is_empty()).then(|| self.dequeue())
+    }
+
+    pub fn try_peek(&self) -> Option<&T> {
+        (!self.is_empty()).then(|| self.peek())
+    }
+
+    pub fn try_peek_mut(&mut self) -> Option<&mut T> {
+        (!self.is_empty()).then(|| self.peek_mut())
+    }
+}
+
+impl<T, P> Drop for CircularLinkedQueue<T, P>
+where
+    P: NodePool<Node<T>>,
+{
+    fn drop(&mut self) {
+        // The sentinel follows the last enqueued node, which is the chain's tail
+        let sentinel = unsafe { (*self.entry).next };
+        let mut next = sentinel;
+        for _ in 0..self.len {
+            unsafe {
+                next = (*next).next;
+                (*next).val.assume_init_drop();
+            }
+        }
+        unsafe {
+            self.allocator
+                .return_chain(sentinel, self.entry, self.len + 1)
+        };
+    }
+}
+
+#[derive(Debug)]
+pub struct DoubleLinkedQueue<T, P = BlockAllocator<BiNode<T>>>
+where
+    P: NodePool<BiNode<T>>,
+{
+    allocator: P,
+    len: usize,
+    entry: *mut BiNode<T>,
+    /// Identifies the queue in its handles, 0 until the first one is given out and
+    /// again once values were split off, which rejects every handle given out before.
+    id: u64,
+}
+
+/// Source of the stamps of [`DoubleLinkedQueue`] values and of queue ids, so that a
+/// stamp is never reused, even across queues.
+#[cfg(target_has_atomic = "64")]
+static NEXT_STAMP: AtomicU64 = AtomicU64::new(1);
+
+#[cfg(feature = "alloc")]
+impl<T> Default for DoubleLinkedQueue<T> {
+    fn default() -> Self {
+        Self::new(
+            BlockAllocator::<Node<T>>::DEFAULT_BLOCK_SIZE,
+            BlockAllocator::<Node<T>>::DEFAULT_BLOCK_CAP,
+        )
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T> DoubleLinkedQueue<T> {
+    pub fn new(block_size: usize, blocks_cap: usize) -> Self {
+        Self::new_in(block_size, blocks_cap, Global)
+    }
+}
+
+impl<T, A> DoubleLinkedQueue<T, BlockAllocator<BiNode<T>, A>>
+where
+    A: Allocator,
+{
+    pub fn new_in(block_size: usize, blocks_cap: usize, alloc: A) -> Self {
+        Self::with_allocator(BlockAllocator::new_in(block_size, blocks_cap, alloc))
+    }
+}
+
+impl<T, P> DoubleLinkedQueue<T, P>
+where
+    P: NodePool<BiNode<T>>,
+{
+    pub fn with_allocator(mut allocator: P) -> Self {
+        let entry: *mut BiNode<_> = allocator.get_node();
+        unsafe { (*entry).next = entry };
+        unsafe { (*entry).prev = entry };
+        Self {
+            allocator,
+            len: 0,
+            entry,
+            id: 0,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        unsafe { self.entry == (*self.entry).next }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn enqueue(&mut self, val: T) {
+        self.try_enqueue(val).unwrap_or_else(|err| err.handle())
+    }
+
+    /// Same as [`try_push_back`](Self::try_push_back).
+    pub fn try_enqueue(&mut self, val: T) -> Result<(), AllocError> {
+        self.try_push_back(val)
+    }
+
+    pub fn try_reserve(&mut self, additional: usize) -> Result<(), AllocError> {
+        self.allocator.try_reserve(additional)
+    }
+
+    pub fn shrink_to_fit(&mut self) {
+        self.allocator.shrink_to_fit();
+    }
+
+    pub fn allocator_stats(&self) -> AllocatorStats {
+        self.allocator.stats()
+    }
+
+    pub fn dequeue(&mut self) -> T {
+        assert!(!self.is_empty(), "underflow: dequeuing from an empty queue");
+        unsafe { self.unlink((*self.entry).prev) }
+    }
+
+    pub fn peek(&self) -> &T {
+        assert!(!self.is_empty(), "underflow: peeking at an empty queue");
+        unsafe { (*(*self.entry).prev).val.assume_init_ref() }
+    }
+
+    pub fn peek_mut(&mut self) -> &mut T {
+        assert!(!self.is_empty(), "underflow: peeking at an empty queue");
+        unsafe { (*(*self.entry).prev).val.assume_init_mut() }
+    }
+
+    pub fn try_dequeue(&mut self) -> Option<T> {
+        (!self.is_empty()).then(|| self.dequeue())
+    }
+
+    pub fn try_peek(&self) -> Option<&T> {
+        (!self.is_empty()).then(|| self.peek())
+    }
+
+    pub fn try_peek_mut(&mut self) -> Option<&mut T> {
+        (!self.is_empty()).then(|| self.peek_mut())
+    }
+
+    pub fn push_front(&mut self, val: T) {
+        self.try_push_front(val).unwrap_or_else(|err| err.handle())
+    }
+
+    pub fn try_push_front(&mut self, val: T) -> Result<(), AllocError> {
+        unsafe { self.link(val, (*self.entry).prev, self.entry)? };
+        Ok(())
+    }
+
+    pub fn push_back(&mut self, val: T) {
+        self.try_push_back(val).unwrap_or_else(|err| err.handle())
+    }
+
+    pub fn try_push_back(&mut self, val: T) -> Result<(), AllocError> {
+        unsafe { self.link(val, self.entry, (*self.entry).next)? };
+        Ok(())
+    }
+
+    /// Same as [`dequeue`](Self::dequeue).
+    pub fn pop_front(&mut self) -> T {
+        assert!(!self.is_empty(), "underflow: popping from an empty queue");
+        unsafe { self.unlink((*self.entry).prev) }
+    }
+
+    pub fn pop_back(&mut self) -> T {
+        assert!(!self.is_empty(), "underflow: popping from an empty queue");
+        unsafe { self.unlink((*self.entry).next) }
+    }
+
+    pub fn try_pop_front(&mut self) -> Option<T> {
+        (!self.is_empty()).then(|| self.pop_front())
+    }
+
+    pub fn try_pop_back(&mut self) -> Option<T> {
+        (!self.is_empty()).then(|| self.pop_back())
+    }
+
+    /// Same as [`peek`](Self::peek).
+    pub fn peek_front(&self) -> &T {
+        self.peek()
+    }
+
+    pub fn peek_back(&self) -> &T {
+        assert!(!self.is_empty(), "underflow: peeking at an empty queue");
+        unsafe { (*(*self.entry).next).val.assume_init_ref() }
+    }
+
+    pub fn peek_back_mut(&mut self) -> &mut T {
+        assert!(!self.is_empty(), "underflow: peeking at an empty queue");
+        unsafe { (*(*self.entry).next).val.assume_init_mut() }
+    }
+
+    pub fn try_peek_back(&self) -> Option<&T> {
+        (!self.is_empty()).then(|| self.peek_back())
+    }
+
+    pub fn try_peek_back_mut(&mut self) -> Option<&mut T> {
+        (!self.is_empty()).then(|| self.peek_back_mut())
+    }
+
+    /// Iterates from the front (next to be dequeued) to the back.
+    pub fn iter(&self) -> DoubleLinkedQueueIter<'_, T> {
+        unsafe {
+            DoubleLinkedQueueIter {
+                front: (*self.entry).prev,
+                back: (*self.entry).next,
+                len: self.len,
+                marker: PhantomData,
+            }
+        }
+    }
+
+    /// Iterates mutably from the front (next to be dequeued) to the back.
+    pub fn iter_mut(&mut self) -> DoubleLinkedQueueIterMut<'_, T> {
+        unsafe {
+            DoubleLinkedQueueIterMut {
+                front: (*self.entry).prev,
+                back: (*self.entry).next,
+                len: self.len,
+                marker: PhantomData,
+            }
+        }
+    }
+
+    /// A cursor on the front value, or on the ghost position if the queue is empty.
+    pub fn cursor_front_mut(&mut self) -> DoubleLinkedQueueCursorMut<'_, T, P> {
+        DoubleLinkedQueueCursorMut {
+            current: unsafe { (*self.entry).prev },
+            index: 0,
+            queue: self,
+        }
+    }
+
+    /// A cursor on the back value, or on the ghost position if the queue is empty.
+    pub fn cursor_back_mut(&mut self) -> DoubleLinkedQueueCursorMut<'_, T, P> {
+        DoubleLinkedQueueCursorMut {
+            current: unsafe { (*self.entry).next },
+            index: self.len.saturating_sub(1),
+            queue: self,
+        }
+    }
+
+    /// Links a new node holding `val` between `prev` and `next`.
+    ///
+    /// From the entry sentinel, `next` links go from the back to the front of the queue
+    /// and `prev` links from the front to the back.
+    unsafe fn link(
+        &mut self,
+        val: T,
+        prev: *mut BiNode<T>,
+        next: *mut BiNode<T>,
+    ) -> Result<*mut BiNode<T>, AllocError> {
+        let node = self.allocator.try_get_node()?;
+        unsafe {
+            (*node).val = MaybeUninit::new(val);
+            (*node).stamp = 0;
+            (*node).prev = prev;
+            (*node).next = next;
+            (*prev).next = node;
+            (*next).prev = node;
+        }
+        self.len += 1;
+        Ok(node)
+    }
+
+    unsafe fn unlink(&mut self, node: *mut BiNode<T>) -> T {
+        unsafe {
+            let val = (*node).val.assume_init_read();
+            // Returned nodes can still be read through stale handles
+            (*node).stamp = 0;
+            (*(*node).prev).next = (*node).next;
+            (*(*node).next).prev = (*node).prev;
+            self.allocator.return_node(node);
+            self.len -= 1;
+            val
+        }
+    }
+}
+
+/// Handles are only accepted when the pool can tell its nodes apart, see [`OwningPool`].
+impl<T, P> DoubleLinkedQueue<T, P>
+where
+    P: OwningPool<BiNode<T>>,
+{
+    #[cfg(target_has_atomic = "64")]
+    pub fn enqueue_with_handle(&mut self, val: T) -> DoubleLinkedQueueHandle<T> {
+        self.try_enqueue_with_handle(val)
+            .unwrap_or_else(|err| err.handle())
+    }
+
+    /// Enqueues `val` and returns a handle to remove it later in O(1), wherever it is.
+    ///
+    /// Splitting values off with [`split_after`](DoubleLinkedQueueCursorMut::split_after)
+    /// rejects every handle of the queue, and values moved by a splice lose their handle.
+    #[cfg(target_has_atomic = "64")]
+    pub fn try_enqueue_with_handle(
+        &mut self,
+        val: T,
+    ) -> Result<DoubleLinkedQueueHandle<T>, AllocError> {
+        let node = unsafe { self.link(val, self.entry, (*self.entry).next)? };
+        if self.id == 0 {
+            self.id = NEXT_STAMP.fetch_add(1, Ordering::Relaxed);
+        }
+        let stamp = NEXT_STAMP.fetch_add(1, Ordering::Relaxed);
+        unsafe { (*node).stamp = stamp };
+        Ok(DoubleLinkedQueueHandle {
+            node: unsafe { NonNull::new_unchecked(node) },
+            stamp,
+            queue: self.id,
+        })
+    }
+
+    /// Takes the value of `handle` out, `None` if it is no longer in the queue.
+    pub fn remove(&mut self, handle: DoubleLinkedQueueHandle<T>) -> Option<T> {
+        let node = self.node(handle)?;
+        Some(unsafe { self.unlink(node) })
+    }
+
+    pub fn contains(&self, handle: DoubleLinkedQueueHandle<T>) -> bool {
+        self.node(handle).is_some()
+    }
+
+    pub fn get(&self, handle: DoubleLinkedQueueHandle<T>) -> Option<&T> {
+        self.node(handle)
+            .map(|node| unsafe { (*node).val.assume_init_ref() })
+    }
+
+    pub fn get_mut(&mut self, handle: DoubleLinkedQueueHandle<T>) -> Option<&mut T> {
+        self.node(handle)
+            .map(|node| unsafe { (*node).val.assume_init_mut() })
+    }
+
+    /// The node of `handle` if it still holds the value it was created for.
+    fn node(&self, handle: DoubleLinkedQueueHandle<T>) -> Option<*mut BiNode<T>> {
+        // Stamps are cleared when values are removed, values moved to another queue are
+        // caught by the queue id, and nodes that the pool owns can be read once returned
+        let node = handle.node.as_ptr();
+        let valid = handle.queue == self.id
+            && self.allocator.owns(node)
+            && unsafe { (*node).stamp } == handle.stamp;
+        valid.then_some(node)
+    }
+}
+
+impl<T, P> Drop for DoubleLinkedQueue<T, P>
+where
+    P: NodePool<BiNode<T>>,
+{
+    fn drop(&mut self) {
+        // Starting from the sentinel, the chain ends with the first enqueued node
+        let mut next = self.entry;
+        for _ in 0..self.len {
+            unsafe {
+                next = (*next).next;
+                (*next).val.assume_init_drop();
+            }
+        }
+        unsafe {
+            self.allocator
+                .return_chain(self.entry, (*self.entry).prev, self.len + 1)
+        };
+    }
+}
+
+/// Iterates over a [`DoubleLinkedQueue`] from front to back, or back to front.
+#[derive(Debug)]
+pub struct DoubleLinkedQueueIter<'a, T> {
+    front: *mut BiNode<T>,
+    back: *mut BiNode<T>,
+    len: usize,
+    marker: PhantomData<&'a T>,
+}
+
+impl<'a, T> Iterator for DoubleLinkedQueueIter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        (self.len > 0).then(|| unsafe {
+            let node = self.front;
+            self.front = (*node).prev;
+            self.len -= 1;
+            (*node).val.assume_init_ref()
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<T> DoubleEndedIterator for DoubleLinkedQueueIter<'_, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        (self.len > 0).then(|| unsafe {
+            let node = self.back;
+            self.back = (*node).next;
+            self.len -= 1;
+            (*node).val.assume_init_ref()
+        })
+    }
+}
+
+impl<T> ExactSizeIterator for DoubleLinkedQueueIter<'_, T> {}
+
+impl<T> FusedIterator for DoubleLinkedQueueIter<'_, T> {}
+
+/// Iterates mutably over a [`DoubleLinkedQueue`] from front to back, or back to front.
+#[derive(Debug)]
+pub struct DoubleLinkedQueueIterMut<'a, T> {
+    front: *mut BiNode<T>,
+    back: *mut BiNode<T>,
+    len: usize,
+    marker: PhantomData<&'a mut T>,
+}
+
+impl<'a, T> Iterator for DoubleLinkedQueueIterMut<'a, T> {
+    type Item = &'a mut T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        (self.len > 0).then(|| unsafe {
+            let node = self.front;
+            self.front = (*node).prev;
+            self.len -= 1;
+            (*node).val.assume_init_mut()
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<T> DoubleEndedIterator for DoubleLinkedQueueIterMut<'_, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        (self.len > 0).then(|| unsafe {
+            let node = self.back;
+            self.back = (*node).next;
+            self.len -= 1;
+            (*node).val.assume_init_mut()
+        })
+    }
+}
+
+impl<T> ExactSizeIterator for DoubleLinkedQueueIterMut<'_, T> {}
+
+impl<T> FusedIterator for DoubleLinkedQueueIterMut<'_, T> {}
+
+/// Dequeues the values of an owned [`DoubleLinkedQueue`], from either end.
+#[derive(Debug)]
+pub struct DoubleLinkedQueueIntoIter<T, P>
+where
+    P: NodePool<BiNode<T>>,
+{
+    queue: DoubleLinkedQueue<T, P>,
+}
+
+impl<T, P> Iterator for DoubleLinkedQueueIntoIter<T, P>
+where
+    P: NodePool<BiNode<T>>,
+{
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.queue.try_pop_front()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.queue.len(), Some(self.queue.len()))
+    }
+}
+
+impl<T, P> DoubleEndedIterator for DoubleLinkedQueueIntoIter<T, P>
+where
+    P: NodePool<BiNode<T>>,
+{
+    fn next_back(&mut self) -> Option<T> {
+        self.queue.try_pop_back()
+    }
+}
+
+impl<T, P> ExactSizeIterator for DoubleLinkedQueueIntoIter<T, P> where P: NodePool<BiNode<T>> {}
+
+impl<T, P> FusedIterator for DoubleLinkedQueueIntoIter<T, P> where P: NodePool<BiNode<T>> {}
+
+impl<T, P> IntoIterator for DoubleLinkedQueue<T, P>
+where
+    P: NodePool<BiNode<T>>,
+{
+    type Item = T;
+    type IntoIter = DoubleLinkedQueueIntoIter<T, P>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        DoubleLinkedQueueIntoIter { queue: self }
+    }
+}
+
+impl<'a, T, P> IntoIterator for &'a DoubleLinkedQueue<T, P>
+where
+    P: NodePool<BiNode<T>>,
+{
+    type Item = &'a T;
+    type IntoIter = DoubleLinkedQueueIter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, T, P> IntoIterator for &'a mut DoubleLinkedQueue<T, P>
+where
+    P: NodePool<BiNode<T>>,
+{
+    type Item = &'a mut T;
+    type IntoIter = DoubleLinkedQueueIterMut<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+/// A reference to a value of a [`DoubleLinkedQueue`], to remove it in O(1).
+///
+/// Handles are plain copyable ids: once their value leaves the queue, they are rejected
+/// instead of aliasing whatever value reuses the node.
+pub struct DoubleLinkedQueueHandle<T> {
+    node: NonNull<BiNode<T>>,
+    stamp: u64,
+    queue: u64,
+}
+
+// Handles are never dereferenced without their queue
+unsafe impl<T> Send for DoubleLinkedQueueHandle<T> {}
+unsafe impl<T> Sync for DoubleLinkedQueueHandle<T> {}
+
+impl<T> Clone for DoubleLinkedQueueHandle<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for DoubleLinkedQueueHandle<T> {}
+
+impl<T> PartialEq for DoubleLinkedQueueHandle<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.node == other.node && self.stamp == other.stamp && self.queue == other.queue
+    }
+}
+
+impl<T> Eq for DoubleLinkedQueueHandle<T> {}
+
+impl<T> Hash for DoubleLinkedQueueHandle<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.node.hash(state);
+        self.stamp.hash(state);
+        self.queue.hash(state);
+    }
+}
+
+impl<T> fmt::Debug for DoubleLinkedQueueHandle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DoubleLinkedQueueHandle")
+            .field("node", &self.node)
+            .field("stamp", &self.stamp)
+            .field("queue", &self.queue)
+            .finish()
+    }
+}
+
+/// A cursor over a [`DoubleLinkedQueue`] that can edit it in O(1) at its position.
+///
+/// Besides values, the cursor can point to a ghost position sitting between the back and
+/// the front of the queue. Moving next goes towards the back.
+#[derive(Debug)]
+pub struct DoubleLinkedQueueCursorMut<'a, T, P>
+where
+    P: NodePool<BiNode<T>>,
+{
+    queue: &'a mut DoubleLinkedQueue<T, P>,
+    current: *mut BiNode<T>,
+    /// Equals the queue length on the ghost position.
+    index: usize,
+}
+
+impl<T, P> DoubleLinkedQueueCursorMut<'_, T, P>
+where
+    P: NodePool<BiNode<T>>,
+{
+    /// The position of the current value from the front, `None` on the ghost position.
+    pub fn index(&self) -> Option<usize> {
+        (!self.is_ghost()).then_some(self.index)
+    }
+
+    pub fn current(&mut self) -> Option<&mut T> {
+        (!self.is_ghost()).then(|| unsafe { (*self.current).val.assume_init_mut() })
+    }
+
+    pub fn peek_next(&mut self) -> Option<&mut T> {
+        let next = unsafe { (*self.current).prev };
+        (next != self.queue.entry).then(|| unsafe { (*next).val.assume_init_mut() })
+    }
+
+    pub fn peek_prev(&mut self) -> Option<&mut T> {
+        let prev = unsafe { (*self.current).next };
+        (prev != self.queue.entry).then(|| unsafe { (*prev).val.assume_init_mut() })
+    }
+
+    /// Moves towards the back, from the back value to the ghost position, and from the
+    /// ghost position to the front value.
+    pub fn move_next(&mut self) {
+        self.current = unsafe { (*self.current).prev };
+        self.index = if self.is_ghost() {
+            self.queue.len
+        } else if self.index == self.queue.len {
+            0
+        } else {
+            self.index + 1
+        };
+    }
+
+    /// Moves towards the front, from the front value to the ghost position, and from the
+    /// ghost position to the back value.
+    pub fn move_prev(&mut self) {
+        self.current = unsafe { (*self.current).next };
+        // From the ghost position, the index goes from the length to the back index
+        self.index = if self.is_ghost() {
+            self.queue.len
+        } else {
+            self.index - 1
+        };
+    }
+
+    /// Inserts `val` right before the current value, or at the back on the ghost position.
+    pub fn insert_before(&mut self, val: T) {
+        self.try_insert_before(val)
+            .unwrap_or_else(|err| err.handle())
+    }
+
+    pub fn try_insert_before(&mut self, val: T) -> Result<(), AllocError> {
+        unsafe { self.queue.link(val, self.current, (*self.current).next)? };
+        self.index += 1;
+        Ok(())
+    }
+
+    /// Inserts `val` right after the current value, or at the front on the ghost position.
+    pub fn insert_after(&mut self, val: T) {
+        self.try_insert_after(val)
+            .unwrap_or_else(|err| err.handle())
+    }
+
+    pub fn try_insert_after(&mut self, val: T) -> Result<(), AllocError> {
+        unsafe { self.queue.link(val, (*self.current).prev, self.current)? };
+        if self.is_ghost() {
+            self.index += 1;
+        }
+        Ok(())
+    }
+
+    /// Removes the current value and moves to the next one, does nothing on the ghost
+    /// position.
+    pub fn remove_current(&mut self) -> Option<T> {
+        if self.is_ghost() {
+            return None;
+        }
+        let node = self.current;
+        self.current = unsafe { (*node).prev };
+        Some(unsafe { self.queue.unlink(node) })
+    }
+
+    /// Moves every value after the current one to a new queue sharing the same pool.
+    ///
+    /// On the ghost position, the whole queue is moved. If any value is moved, every
+    /// handle to the queue is rejected afterwards, see
+    /// [`enqueue_with_handle`](DoubleLinkedQueue::enqueue_with_handle).
+    pub fn split_after(&mut self) -> DoubleLinkedQueue<T, P>
+    where
+        P: Clone,
+    {
+        self.try_split_after().unwrap_or_else(|err| err.handle())
+    }
+
+    pub fn try_split_after(&mut self) -> Result<DoubleLinkedQueue<T, P>, AllocError>
+    where
+        P: Clone,
+    {
+        let mut allocator = self.queue.allocator.clone();
+        assert!(
+            allocator.shares_nodes_with(&self.queue.allocator),
+            "split: the pool of the queue is not shared by its clones"
+        );
+        let entry: *mut BiNode<T> = allocator.try_get_node()?;
+        let moved = if self.is_ghost() {
+            self.queue.len
+        } else {
+            self.queue.len - self.index - 1
+        };
+        unsafe {
+            if moved == 0 {
+                (*entry).next = entry;
+                (*entry).prev = entry;
+            } else {
+                let first = (*self.current).prev;
+                let last = (*self.queue.entry).next;
+                // Only the queue id can tell which handles were moved in O(1)
+                self.queue.id = 0;
+                (*entry).prev = first;
+                (*first).next = entry;
+                (*entry).next = last;
+                (*last).prev = entry;
+                (*self.current).prev = self.queue.entry;
+                (*self.queue.entry).next = self.current;
+            }
+        }
+        self.queue.len -= moved;
+        if self.is_ghost() {
+            self.index = 0;
+        }
+        Ok(DoubleLinkedQueue {
+            allocator,
+            len: moved,
+            entry,
+            id: 0,
+        })
+    }
+
+    /// Moves every value of `other` right after the current value, or at the front on the
+    /// ghost position.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `other` does not draw its nodes from the same pool.
+    pub fn splice_after(&mut self, mut other: DoubleLinkedQueue<T, P>) {
+        if let Some((first, last, moved)) = self.take_all(&mut other) {
+            unsafe {
+                let next = (*self.current).prev;
+                (*self.current).prev = first;
+                (*first).next = self.current;
+                (*last).prev = next;
+                (*next).next = last;
+            }
+            if self.is_ghost() {
+                self.index += moved;
+            }
+        }
+    }
+
+    /// Moves every value of `other` right before the current value, or at the back on the
+    /// ghost position.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `other` does not draw its nodes from the same pool.
+    pub fn splice_before(&mut self, mut other: DoubleLinkedQueue<T, P>) {
+        if let Some((first, last, moved)) = self.take_all(&mut other) {
+            unsafe {
+                let prev = (*self.current).next;
+                (*prev).prev = first;
+                (*first).next = prev;
+                (*last).prev = self.current;
+                (*self.current).next = last;
+            }
+            self.index += moved;
+        }
+    }
+
+    fn is_ghost(&self) -> bool {
+        self.current == self.queue.entry
+    }
+
+    /// Unlinks the values of `other`, returning its front and back nodes and its length.
+    fn take_all(
+        &mut self,
+        other: &mut DoubleLinkedQueue<T, P>,
+    ) -> Option<(*mut BiNode<T>, *mut BiNode<T>, usize)> {
+        if other.is_empty() {
+            return None;
+        }
+        assert!(
+            self.queue.allocator.shares_nodes_with(&other.allocator),
+            "splice: queues do not share the same pool"
+        );
+        let moved = mem::take(&mut other.len);
+        self.queue.len += moved;
+        unsafe {
+            let first = (*other.entry).prev;
+            let last = (*other.entry).next;
+            (*other.entry).prev = other.entry;
+            (*other.entry).next = other.entry;
+            Some((first, last, moved))
+        }
+    }
+}
+
+/// A queue that keeps the aggregate of its values, from front to back, in amortized O(1).
+///
+/// Values are enqueued on a back [`AggregateStack`] and dequeued from a front one, which
+/// is refilled from the back one once empty. This makes sliding window minimums, sums or
+/// gcds O(1) amortized per step.
+///
+/// By default both stacks draw their nodes from one [`SharedPool`], so that refilling the
+/// front only reuses the nodes released by the back.
+#[derive(Debug)]
+pub struct AggregateQueue<T, M, S = PooledStack<T, <M as Monoid<T>>::Output>>
+where
+    M: Monoid<T>,
+    S: Stack<(T, M::Output)>,
+{
+    /// Values from the front (top) to the back, folded from the top.
+    front: AggregateStack<T, Reversed<M>, S>,
+    back: AggregateStack<T, M, S>,
+}
+
+/// The stacks of an [`AggregateQueue`], sharing their nodes when possible.
+#[cfg(feature = "alloc")]
+type PooledStack<T, O> = LinkedListStack<(T, O), SharedPool<Node<(T, O)>>>;
+
+#[cfg(not(feature = "alloc"))]
+type PooledStack<T, O> = LinkedListStack<(T, O)>;
+
+/// A queue that keeps track of its minimum, e.g. over a sliding window.
+pub type MinQueue<T, S = PooledStack<T, Option<T>>> = AggregateQueue<T, Min, S>;
+
+/// A queue that keeps track of its maximum, e.g. over a sliding window.
+pub type MaxQueue<T, S = PooledStack<T, Option<T>>> = AggregateQueue<T, Max, S>;
+
+/// Combines its operands in reverse order, for stacks holding values from the top.
+#[derive(Debug)]
+struct Reversed<M>(M);
+
+impl<T, M> Monoid<T> for Reversed<M>
+where
+    M: Monoid<T>,
+{
+    type Output = M::Output;
+
+    fn empty(&self) -> M::Output {
+        self.0.empty()
+    }
+
+    fn lift(&self, val: &T) -> M::Output {
+        self.0.lift(val)
+    }
+
+    fn combine(&self, lhs: &M::Output, rhs: &M::Output) -> M::Output {
+        self.0.combine(rhs, lhs)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T, M> Default for AggregateQueue<T, M>
+where
+    M: Monoid<T> + Default + Clone,
+{
+    fn default() -> Self {
+        Self::with_monoid(M::default())
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T, M> AggregateQueue<T, M>
+where
+    M: Monoid<T> + Clone,
+{
+    pub fn new() -> Self
+    where
+        M: Default,
+    {
+        Self::default()
+    }
+
+    pub fn with_monoid(monoid: M) -> Self {
+        let pool = SharedPool::new(
+            BlockAllocator::<Node<(T, M::Output)>>::DEFAULT_BLOCK_SIZE,
+            BlockAllocator::<Node<(T, M::Output)>>::DEFAULT_BLOCK_CAP,
+        );
+        Self::with_stacks(
+            monoid,
+            LinkedListStack::with_allocator(pool.clone()),
+            LinkedListStack::with_allocator(pool),
+        )
+    }
+}
+
+impl<T, M, S> AggregateQueue<T, M, S>
+where
+    M: Monoid<T>,
+    S: Stack<(T, M::Output)>,
+{
+    /// Refilling the front must not fail, so it must hold at least as many values as the
+    /// back. Unbounded stacks should share their memory, as the default ones do.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a stack is not empty, or if the front can hold fewer values than the back.
+    pub fn with_stacks(monoid: M, front: S, back: S) -> Self
+    where
+        M: Clone,
+    {
+        let fits = match (front.max_len(), back.max_len()) {
+            (Some(front_len), Some(back_len)) => front_len >= back_len,
+            (front_len, back_len) => front_len.is_none() || back_len.is_some(),
+        };
+        assert!(fits, "aggregate queue front smaller than its back");
+        Self {
+            front: AggregateStack::with_stack(Reversed(monoid.clone()), front),
+            back: AggregateStack::with_stack(monoid, back),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.front.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.front.len() + self.back.len()
+    }
+
+    pub fn enqueue(&mut self, val: T) {
+        // The front is only empty along with the back
+        if self.front.is_empty() {
+            self.front.push(val);
+        } else {
+            self.back.push(val);
+        }
+    }
+
+    pub fn try_enqueue(&mut self, val: T) -> Result<(), <S::Error as AggregateError<T>>::Error>
+    where
+        S::Error: AggregateError<T>,
+    {
+        if self.front.is_empty() {
+            self.front.try_push(val)
+        } else {
+            self.back.try_push(val)
+        }
+    }
+
+    pub fn dequeue(&mut self) -> T {
+        assert!(!self.is_empty(), "underflow: dequeuing from an empty queue");
+        let val = self.front.pop();
+        if self.front.is_empty() {
+            // Each pop makes room for the next push, see `with_stacks`
+            while let Some(val) = self.back.try_pop() {
+                self.front.push(val);
+            }
+        }
+        val
+    }
+
+    pub fn peek(&self) -> &T {
+        assert!(!self.is_empty(), "underflow: peeking at an empty queue");
+        self.front.peek()
+    }
+
+    pub fn try_dequeue(&mut self) -> Option<T> {
+        (!self.is_empty()).then(|| self.dequeue())
+    }
+
+    pub fn try_peek(&self) -> Option<&T> {
+        self.front.try_peek()
+    }
+
+    /// The aggregate of every value, from the front of the queue to its back.
+    pub fn aggregate(&self) -> M::Output {
+        let monoid = self.back.monoid();
+        match (self.front.top_aggregate(), self.back.top_aggregate()) {
+            (Some(front), Some(back)) => monoid.combine(front, back),
+            (Some(front), None) => front.clone(),
+            (None, _) => monoid.empty(),
+        }
+    }
+}
+
+impl<T, S> AggregateQueue<T, Min, S>
+where
+    T: Ord + Clone,
+    S: Stack<(T, Option<T>)>,
+{
+    pub fn min(&self) -> Option<&T> {
+        let front = self.front.top_aggregate().and_then(Option::as_ref);
+        let back = self.back.min();
+        front.into_iter().chain(back).min()
+    }
+}
+
+impl<T, S> AggregateQueue<T, Max, S>
+where
+    T: Ord + Clone,
+    S: Stack<(T, Option<T>)>,
+{
+    pub fn max(&self) -> Option<&T> {
+        let front = self.front.top_aggregate().and_then(Option::as_ref);
+        let back = self.back.max();
+        front.into_iter().chain(back).max()
+    }
+}
+
+/// A sliding window that keeps track of its minimum in amortized O(1), without cloning.
+///
+/// Only the values that can still become the minimum are kept, in increasing order along
+/// with their position: enqueuing drops every kept value greater than the new one, which
+/// is then never given back. For a maximum, enqueue [`Reverse`](core::cmp::Reverse)
+/// values.
+#[derive(Debug)]
+pub struct MonotonicQueue<T, P = BlockAllocator<BiNode<(usize, T)>>>
+where
+    P: NodePool<BiNode<(usize, T)>>,
+{
+    kept: DoubleLinkedQueue<(usize, T), P>,
+    /// Positions of the oldest value of the window and of the next one to be enqueued.
+    front: usize,
+    back: usize,
+}
+
+#[cfg(feature = "alloc")]
+impl<T> Default for MonotonicQueue<T>
+where
+    T: Ord,
+{
+    fn default() -> Self {
+        Self::new(
+            BlockAllocator::<BiNode<(usize, T)>>::DEFAULT_BLOCK_SIZE,
+            BlockAllocator::<BiNode<(usize, T)>>::DEFAULT_BLOCK_CAP,
+        )
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T> MonotonicQueue<T>
+where
+    T: Ord,
+{
+    pub fn new(block_size: usize, blocks_cap: usize) -> Self {
+        Self::new_in(block_size, blocks_cap, Global)
+    }
+}
+
+impl<T, A> MonotonicQueue<T, BlockAllocator<BiNode<(usize, T)>, A>>
+where
+    T: Ord,
+    A: Allocator,
+{
+    pub fn new_in(block_size: usize, blocks_cap: usize, alloc: A) -> Self {
+        Self::with_allocator(BlockAllocator::new_in(block_size, blocks_cap, alloc))
+    }
+}
+
+impl<T, P> MonotonicQueue<T, P>
+where
+    T: Ord,
+    P: NodePool<BiNode<(usize, T)>>,
+{
+    pub fn with_allocator(allocator: P) -> Self {
+        Self {
+            kept: DoubleLinkedQueue::with_allocator(allocator),
+            front: 0,
+            back: 0,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The number of values in the window, kept or not.
+    pub fn len(&self) -> usize {
+        self.back.wrapping_sub(self.front)
+    }
+
+    /// The number of values that can still become the minimum.
+    pub fn kept_len(&self) -> usize {
+        self.kept.len()
+    }
+
+    pub fn enqueue(&mut self, val: T) {
+        self.try_enqueue(val).unwrap_or_else(|err| err.handle())
+    }
+
+    pub fn try_enqueue(&mut self, val: T) -> Result<(), AllocError> {
+        // Reserved first, dropped values would otherwise be lost for nothing
+        self.kept.try_reserve(1)?;
+        while self
+            .kept
+            .try_peek_back()
+            .is_some_and(|(_, kept)| kept > &val)
+        {
+            self.kept.pop_back();
+        }
+        self.kept.push_back((self.back, val));
+        self.back = self.back.wrapping_add(1);
+        Ok(())
+    }
+
+    /// Slides the oldest value out of the window, returning it if it was still kept.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the window is empty.
+    pub fn dequeue(&mut self) -> Option<T> {
+        assert!(!self.is_empty(), "underflow: dequeuing from an empty queue");
+        let oldest = self.front;
+        self.front = self.front.wrapping_add(1);
+        if self.kept.try_peek().is_some_and(|(pos, _)| *pos == oldest) {
+            Some(self.kept.pop_front().1)
+        } else {
+            None
+        }
+    }
+
+    /// The minimum of the window, its oldest value if several are equal.
+    pub fn min(&self) -> Option<&T> {
+        self.kept.try_peek().map(|(_, val)| val)
+    }
+}
+
+/// Implements [`Queue`] for a queue by forwarding to its inherent methods.
+macro_rules! impl_queue {
+    ([$($params:tt)*] $queue:ty, $error:ty) => {
+        impl<$($params)*> Queue<T> for $queue {
+            type Error = $error;
+
+            fn is_empty(&self) -> bool {
+                <$queue>::is_empty(self)
+            }
+
+            fn len(&self) -> usize {
+                <$queue>::len(self)
+            }
+
+            fn enqueue(&mut self, val: T) {
+                <$queue>::enqueue(self, val)
+            }
+
+            fn try_enqueue(&mut self, val: T) -> Result<(), Self::Error> {
+                <$queue>::try_enqueue(self, val)
+            }
+
+            fn dequeue(&mut self) -> T {
+                <$queue>::dequeue(self)
+            }
+
+            fn peek(&self) -> &T {
+                <$queue>::peek(self)
+            }
+
+            fn peek_mut(&mut self) -> &mut T {
+                <$queue>::peek_mut(self)
+            }
+        }
+    };
+}
+
+impl_queue!([T, A: Allocator] BoundedQueue<T, A>, T);
+impl_queue!([T, A: Allocator] RingQueue<T, A>, AllocError);
+impl_queue!([T, A: Allocator] ShadowCopyQueue<T, A>, AllocError);
+impl_queue!([T, A: Allocator + Clone] RealTimeQueue<T, A>, AllocError);
+impl_queue!([T, P: NodePool<Node<T>>] LinkedListQueue<T, P>, AllocError);
+impl_queue!([T, P: NodePool<Node<T>>] CircularLinkedQueue<T, P>, AllocError);
+impl_queue!([T, P: NodePool<BiNode<T>>] DoubleLinkedQueue<T, P>, AllocError);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn bounded_queue_ok() {
+        let mut q = BoundedQueue::new(6);
+        q.enqueue(3);
+        q.enqueue(2);
+        q.enqueue(1);
+        assert_eq!(&3, q.peek());
+        assert_eq!(3, q.len());
+        assert_eq!(3, q.dequeue());
+
+        q.dequeue();
+        q.dequeue();
+        assert!(q.is_empty());
+
+        let range = 4..=9;
+        for (j, i) in range.clone().enumerate() {
+            assert_eq!(j, q.len());
+            q.enqueue(i);
+        }
+        assert_eq!(range.clone().count(), q.len());
+        for i in range {
+            assert_eq!(i, q.dequeue());
+        }
+        assert!(q.is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    #[should_panic(expected = "underflow: dequeuing from an empty queue")]
+    fn bounded_queue_underflow() {
+        let mut q = BoundedQueue::new(1);
+        q.enqueue(1);
+        q.dequeue();
+        assert!(q.is_empty());
+        q.dequeue();
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    #[should_panic(expected = "overflow: enqueuing to a full queue")]
+    fn bounded_queue_overflow() {
+        let mut q = BoundedQueue::new(1);
+        q.enqueue(1);
+        q.enqueue(2);
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn linked_list_queue_ok() {
+        let mut q = LinkedListQueue::new(2, 1);
+        q.enqueue(3);
+        q.enqueue(2);
+        q.enqueue(1);
+        assert_eq!(&3, q.peek());
+        assert_eq!(3, q.len());
+        assert_eq!(3, q.dequeue());
+
+        q.dequeue();
+        q.dequeue();
+        assert!(q.is_empty());
+
+        let range = 4..=9;
+        for (j, i) in range.clone().enumerate() {
+            assert_eq!(j, q.len());
+            q.enqueue(i);
+        }
+        assert_eq!(range.clone().count(), q.len());
+        for i in range {
+            assert_eq!(i, q.dequeue());
+        }
+        assert!(q.is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    #[should_panic(expected = "underflow: dequeuing from an empty queue")]
+    fn linked_list_queue_underflow() {
+        let mut q = LinkedListQueue::new(4, 2);
+        q.enqueue(1);
+        q.dequeue();
+        assert!(q.is_empty());
+        q.dequeue();
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn circular_linked_queue_ok() {
+        let mut q = CircularLinkedQueue::new(2, 1);
+        q.enqueue(3);
+        q.enqueue(2);
+        q.enqueue(1);
+        assert_eq!(&3, q.peek());
+        assert_eq!(3, q.len());
+        assert_eq!(3, q.dequeue());
+
+        q.dequeue();
+        q.dequeue();
+        assert!(q.is_empty());
+
+        let range = 4..=9;
+        for (j, i) in range.clone().enumerate() {
+            assert_eq!(j, q.len());
+            q.enqueue(i);
+        }
+        assert_eq!(range.clone().count(), q.len());
+        for i in range {
+            assert_eq!(i, q.dequeue());
+        }
+        assert!(q.is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    #[should_panic(expected = "underflow: dequeuing from an empty queue")]
+    fn circular_linked_queue_underflow() {
+        let mut q = CircularLinkedQueue::new(4, 2);
+        q.enqueue(1);
+        q.dequeue();
+        assert!(q.is_empty());
+        q.dequeue();
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn double_linked_queue_ok() {
+        let mut q = DoubleLinkedQueue::new(2, 1);
+        q.enqueue(3);
+        q.enqueue(2);
+        q.enqueue(1);
+        assert_eq!(&3, q.peek());
+        assert_eq!(3, q.len());
+        assert_eq!(3, q.dequeue());
+
+        q.dequeue();
+        q.dequeue();
+        assert!(q.is_empty());
+
+        let range = 4..=9;
+        for (j, i) in range.clone().enumerate() {
+            assert_eq!(j, q.len());
+            q.enqueue(i);
+        }
+        assert_eq!(range.clone().count(), q.len());
+        for i in range {
+            assert_eq!(i, q.dequeue());
+        }
+        assert!(q.is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn double_linked_queue_deque() {
+        let mut q = DoubleLinkedQueue::new(2, 1);
+        q.push_back(2);
+        q.push_front(1);
+        q.push_back(3);
+        q.push_front(0);
+        assert_eq!(&0, q.peek_front());
+        assert_eq!(&3, q.peek_back());
+        assert_eq!(4, q.len());
+        assert!(q.iter().copied().eq(0..4));
+        assert!(q.iter().rev().copied().eq((0..4).rev()));
+
+        for val in q.iter_mut() {
+            *val *= 10;
+        }
+        *q.peek_back_mut() += 1;
+        assert_eq!(31, q.pop_back());
+        assert_eq!(0, q.pop_front());
+
+        let mut iter = q.iter();
+        assert_eq!(Some(&10), iter.next());
+        assert_eq!(Some(&20), iter.next_back());
+        assert_eq!(None, iter.next());
+        assert_eq!(None, iter.next_back());
+
+        q.enqueue(30);
+        let mut iter = q.into_iter();
+        assert_eq!(3, iter.len());
+        assert_eq!(Some(30), iter.next_back());
+        assert_eq!(Some(10), iter.next());
+        assert_eq!(Some(20), iter.next());
+        assert_eq!(None, iter.next_back());
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn double_linked_queue_deque_empty() {
+        let mut q = DoubleLinkedQueue::<u32>::new(2, 1);
+        assert_eq!(None, q.try_pop_front());
+        assert_eq!(None, q.try_pop_back());
+        assert_eq!(None, q.try_peek_back());
+        assert_eq!(None, q.iter().next_back());
+        q.push_front(1);
+        assert_eq!(Some(&1), q.try_peek_back());
+        assert_eq!(Some(1), q.try_pop_back());
+        assert!(q.is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn double_linked_queue_cursor() {
+        let mut q = DoubleLinkedQueue::new(4, 1);
+        q.enqueue(1);
+        q.enqueue(2);
+        q.enqueue(4);
+        let mut cursor = q.cursor_front_mut();
+        assert_eq!(Some(0), cursor.index());
+        cursor.insert_before(0);
+        assert_eq!(Some(1), cursor.index());
+        cursor.move_next();
+        cursor.insert_after(3);
+        assert_eq!(Some(&mut 2), cursor.current());
+        assert_eq!(Some(&mut 3), cursor.peek_next());
+        assert_eq!(Some(&mut 1), cursor.peek_prev());
+
+        // Back to front through the ghost position
+        cursor.move_next();
+        cursor.move_next();
+        cursor.move_next();
+        assert_eq!(None, cursor.index());
+        assert_eq!(None, cursor.current());
+        assert_eq!(None, cursor.remove_current());
+        cursor.insert_before(5);
+        cursor.insert_after(-1);
+        cursor.move_next();
+        assert_eq!(Some(0), cursor.index());
+        assert_eq!(Some(-1), cursor.remove_current());
+        assert_eq!(Some(&mut 0), cursor.current());
+        cursor.move_prev();
+        cursor.move_prev();
+        assert_eq!(Some(5), cursor.index());
+        assert_eq!(Some(5), cursor.remove_current());
+        assert_eq!(None, cursor.index());
+        assert!(q.iter().copied().eq(0..5));
+
+        let mut cursor = q.cursor_back_mut();
+        assert_eq!(Some(4), cursor.index());
+        while cursor.current().is_some() {
+            if *cursor.current().unwrap() % 2 == 0 {
+                cursor.remove_current();
+                cursor.move_prev();
+            } else {
+                cursor.move_prev();
+            }
+        }
+        assert!(q.iter().copied().eq([1, 3]));
+        assert!(q.iter().rev().copied().eq([3, 1]));
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn double_linked_queue_split_splice() {
+        use crate::allocator::SharedPool;
+        let pool = SharedPool::new(4, 1);
+        let mut q = DoubleLinkedQueue::with_allocator(pool.clone());
+        for i in 0..6 {
+            q.enqueue(i);
+        }
+
+        let mut cursor = q.cursor_front_mut();
+        cursor.move_next();
+        let mut tail = cursor.split_after();
+        assert_eq!(Some(1), cursor.index());
+        cursor.move_next();
+        assert_eq!(None, cursor.index());
+        assert_eq!(2, q.len());
+        assert_eq!(4, tail.len());
+        assert!(tail.iter().copied().eq(2..6));
+        assert!(tail.iter().rev().copied().eq((2..6).rev()));
+
+        let mut cursor = tail.cursor_front_mut();
+        cursor.move_next();
+        cursor.splice_after(q);
+        assert_eq!(Some(1), cursor.index());
+        cursor.splice_before(DoubleLinkedQueue::with_allocator(pool.clone()));
+        let mut other = DoubleLinkedQueue::with_allocator(pool.clone());
+        other.enqueue(10);
+        other.enqueue(11);
+        cursor.splice_before(other);
+        assert_eq!(Some(3), cursor.index());
+        assert_eq!(Some(&mut 3), cursor.current());
+        cursor.move_prev();
+        cursor.move_prev();
+        cursor.move_prev();
+        cursor.move_prev();
+        assert_eq!(None, cursor.index());
+        let mut other = DoubleLinkedQueue::with_allocator(pool.clone());
+        other.enqueue(12);
+        cursor.splice_after(other);
+        assert_eq!(None, cursor.index());
+        cursor.move_next();
+        assert_eq!(Some(&mut 12), cursor.current());
+        assert!(tail.iter().copied().eq([12, 2, 10, 11, 3, 0, 1, 4, 5]));
+        assert!(tail
+            .iter()
+            .rev()
+            .copied()
+            .eq([5, 4, 1, 0, 3, 11, 10, 2, 12]));
+
+        let mut cursor = tail.cursor_back_mut();
+        cursor.move_next();
+        let all = cursor.split_after();
+        assert!(tail.is_empty());
+        assert_eq!(9, all.len());
+        drop(all);
+        drop(tail);
+        assert_eq!(0, pool.stats().live_nodes);
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn double_linked_queue_handles() {
+        let mut q = DoubleLinkedQueue::new(2, 1);
+        let handles: [_; 4] = core::array::from_fn(|i| q.enqueue_with_handle(i));
+        q.enqueue(4);
+        assert_eq!(Some(2), q.remove(handles[2]));
+        assert!(!q.contains(handles[2]));
+        assert_eq!(None, q.remove(handles[2]));
+        *q.get_mut(handles[3]).unwrap() = 30;
+        assert_eq!(Some(&1), q.get(handles[1]));
+        assert_eq!(0, q.dequeue());
+        assert_eq!(None, q.get(handles[0]));
+
+        // Nodes are reused, stale handles still see nothing
+        let handle = q.enqueue_with_handle(5);
+        assert_eq!(None, q.remove(handles[0]));
+        assert_eq!(None, q.remove(handles[2]));
+        assert!(q.iter().copied().eq([1, 30, 4, 5]));
+        assert_eq!(Some(5), q.remove(handle));
+
+        let mut other = DoubleLinkedQueue::new(2, 1);
+        other.enqueue_with_handle(6);
+        assert_eq!(None, other.remove(handles[1]));
+        assert_eq!(Some(1), q.remove(handles[1]));
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn double_linked_queue_handles_moved() {
+        use crate::allocator::SharedPool;
+        let mut q = DoubleLinkedQueue::with_allocator(SharedPool::new(4, 1));
+        let handles: [_; 4] = core::array::from_fn(|i| q.enqueue_with_handle(i));
+        let mut cursor = q.cursor_front_mut();
+        cursor.move_next();
+        let mut back = cursor.split_after();
+        // Splitting rejects every handle of the split queue, moved or not
+        assert!(!q.contains(handles[1]));
+        assert!(!q.contains(handles[2]));
+        assert!(!back.contains(handles[2]));
+
+        let handle = back.enqueue_with_handle(4);
+        let kept = q.enqueue_with_handle(5);
+        q.cursor_back_mut().splice_after(back);
+        assert!(!q.contains(handle));
+        assert_eq!(Some(5), q.remove(kept));
+        assert_eq!(5, q.len());
+        assert!(q.into_iter().eq(0..5));
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    #[should_panic(expected = "splice: queues do not share the same pool")]
+    fn double_linked_queue_splice_foreign() {
+        let mut q = DoubleLinkedQueue::new(4, 1);
+        let mut other = DoubleLinkedQueue::new(4, 1);
+        other.enqueue(1);
+        q.cursor_front_mut().splice_after(other);
+    }
+
+    #[test]
+    fn double_linked_queue_region() {
+        static mut REGION: [MaybeUninit<u8>; 512] = [MaybeUninit::uninit(); 512];
+        let region = unsafe { &mut *ptr::addr_of_mut!(REGION) };
+        let mut q = DoubleLinkedQueue::with_allocator(BlockAllocator::from_region(region, 4, 2));
+        let mut enqueued = 0;
+        while q.try_enqueue(enqueued).is_ok() {
+            enqueued += 1;
+        }
+        assert!(enqueued > 0);
+        for i in 0..enqueued {
+            assert_eq!(i, q.dequeue());
+        }
+        assert!(q.is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn linked_queues_shared_pool() {
+        use crate::allocator::SharedPool;
+        let pool = SharedPool::new(8, 1);
+        let mut queues: [_; 4] =
+            core::array::from_fn(|_| LinkedListQueue::with_allocator(pool.clone()));
+        for (i, q) in queues.iter_mut().enumerate() {
+            q.enqueue(i);
+            q.enqueue(i + 1);
+        }
+        assert_eq!(8, queues[0].allocator_stats().live_nodes);
+        assert_eq!(1, queues[3].allocator_stats().blocks);
+        for (i, q) in queues.iter_mut().enumerate() {
+            assert_eq!(i, q.dequeue());
+        }
+        let mut circular = CircularLinkedQueue::with_allocator(pool.clone());
+        for i in 0..4 {
+            circular.enqueue(i);
+        }
+        let stats = circular.allocator_stats();
+        assert_eq!(9, stats.live_nodes);
+        assert_eq!(2, stats.blocks);
+        drop(queues);
+        drop(circular);
+        assert_eq!(0, pool.stats().live_nodes);
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn ring_queue_ok() {
+        let mut q = RingQueue::new(3);
+        q.enqueue(0);
+        q.enqueue(1);
+        assert_eq!(0, q.dequeue());
+        // Wrapped around, then unwrapped by growing
+        for i in 2..6 {
+            q.enqueue(i);
+        }
+        assert_eq!(6, q.capacity());
+        assert_eq!(5, q.len());
+        assert_eq!(&1, q.peek());
+        for i in 1..6 {
+            assert_eq!(i, q.dequeue());
+        }
+        assert!(q.is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn shadow_copy_queue_ok() {
+        use std::boxed::Box;
+
+        let mut q: ShadowCopyQueue<Box<u32>> = ShadowCopyQueue::new(8);
+        let mut expected = RingQueue::new(1);
+        // Deterministic mix of enqueues and dequeues, mostly growing
+        let mut seed = 7u32;
+        for i in 0..500 {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            if seed.is_multiple_of(3) && !q.is_empty() {
+                assert_eq!(expected.dequeue(), *q.dequeue());
+            } else {
+                q.enqueue(Box::new(i));
+                expected.enqueue(i);
+            }
+            if !q.copy.is_null() && q.copy_front < q.copy_end {
+                // The front value lives in the copy, mutations must land there
+                **q.peek_mut() += 1000;
+                *expected.peek_mut() += 1000;
+            }
+            assert_eq!(expected.len(), q.len());
+            assert!(q.len() < q.capacity());
+        }
+        while let Some(val) = q.try_dequeue() {
+            assert_eq!(expected.dequeue(), *val);
+        }
+        assert!(expected.is_empty());
+
+        // Whatever the capacity, the copy completes before the ring gets full
+        for max_size in 1..100 {
+            let mut q = ShadowCopyQueue::new(max_size);
+            for i in 0..4 * max_size {
+                q.enqueue(i);
+                assert!(q.len() < q.capacity());
+            }
+        }
+
+        // Dropped halfway through a copy
+        let mut q = ShadowCopyQueue::new(8);
+        for i in 0..6 {
+            q.enqueue(Box::new(i));
+        }
+        assert!(!q.copy.is_null());
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn real_time_queue_ok() {
+        let mut q = RealTimeQueue::new(2);
+        q.enqueue(3);
+        q.enqueue(2);
+        q.enqueue(1);
+        assert_eq!(&3, q.peek());
+        assert_eq!(3, q.len());
+        assert_eq!(3, q.dequeue());
+        q.dequeue();
+        q.dequeue();
+        assert!(q.is_empty());
+
+        // Interleave operations so that rotations start on both enqueues and dequeues
+        let (mut enqueued, mut dequeued) = (0, 0);
+        let mut seed = 7u32;
+        for _ in 0..2000 {
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+            if q.is_empty() || (seed >> 16) % 5 < 3 {
+                q.enqueue(enqueued);
+                enqueued += 1;
+            } else {
+                assert_eq!(&dequeued, q.peek());
+                assert_eq!(dequeued, q.dequeue());
+                dequeued += 1;
+            }
+            assert_eq!(enqueued - dequeued, q.len());
+        }
+        while let Some(val) = q.try_dequeue() {
+            assert_eq!(dequeued, val);
+            dequeued += 1;
+        }
+        assert_eq!(enqueued, dequeued);
+    }
+
+    #[test]
+    fn queues_try_new_in_region() {
+        use crate::allocator::Region;
+        static mut REGION: [MaybeUninit<u8>; 64] = [MaybeUninit::uninit(); 64];
+        let region = Region::new(unsafe { &mut *ptr::addr_of_mut!(REGION) });
+        let too_large = |res: Result<(), AllocError>| {
+            assert!(matches!(res, Err(AllocError::AllocFailed { .. })))
+        };
+        too_large(BoundedQueue::<u64, _>::try_new_in(16, &region).map(drop));
+        too_large(RingQueue::<u64, _>::try_new_in(16, &region).map(drop));
+        too_large(ShadowCopyQueue::<u64, _>::try_new_in(16, &region).map(drop));
+        too_large(RealTimeQueue::<u64, _>::try_new_in(16, &region).map(drop));
+        assert_eq!(
+            Some(AllocError::CapacityOverflow),
+            BoundedQueue::<u64, _>::try_new_in(usize::MAX, &region).err()
+        );
+
+        let mut q = RingQueue::try_new_in(4, &region).unwrap();
+        q.enqueue(1);
+        assert_eq!(1, q.dequeue());
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn real_time_queue_dequeue_no_alloc() {
+        use core::cell::Cell;
+
+        /// Counts the allocations made through it.
+        #[derive(Default)]
+        struct Counting(Cell<usize>);
+
+        unsafe impl Allocator for Counting {
+            fn allocate(&self, layout: Layout) -> Result<NonNull<u8>, AllocError> {
+                self.0.set(self.0.get() + 1);
+                Global.allocate(layout)
+            }
+
+            unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+                unsafe { Global.deallocate(ptr, layout) }
+            }
+        }
+
+        let counting = Counting::default();
+        let mut q = RealTimeQueue::new_in(2, &counting);
+        for i in 0..64 {
+            q.enqueue(i);
+        }
+        let allocated = counting.0.get();
+        for i in 0..64 {
+            assert_eq!(i, q.dequeue());
+        }
+        assert_eq!(allocated, counting.0.get());
+
+        // Interleaved with enqueues, which may allocate
+        let (mut enqueued, mut dequeued) = (0, 0);
+        for round in 0..300 {
+            q.enqueue(enqueued);
+            enqueued += 1;
+            if round % 3 != 0 {
+                let allocated = counting.0.get();
+                assert_eq!(dequeued, q.dequeue());
+                dequeued += 1;
+                assert_eq!(allocated, counting.0.get());
+            }
+        }
+    }
+
+    #[test]
+    fn real_time_queue_region() {
+        use crate::allocator::Region;
+        static mut REGION: [MaybeUninit<u8>; 4096] = [MaybeUninit::uninit(); 4096];
+        let region = Region::new(unsafe { &mut *ptr::addr_of_mut!(REGION) });
+        let mut q = RealTimeQueue::new_in(4, &region);
+        // Once the region is exhausted, dequeues keep working without allocating
+        let (mut enqueued, mut dequeued) = (0, 0);
+        let mut full = 0;
+        for _ in 0..500 {
+            if q.try_enqueue(enqueued).is_ok() {
+                enqueued += 1;
+            } else {
+                full += 1;
+                assert_eq!(Some(dequeued), q.try_dequeue());
+                dequeued += 1;
+            }
+        }
+        assert!(full > 0);
+        while let Some(val) = q.try_dequeue() {
+            assert_eq!(dequeued, val);
+            dequeued += 1;
+        }
+        assert_eq!(enqueued, dequeued);
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn real_time_queue_drop() {
+        use std::rc::Rc;
+        let val = Rc::new(());
+        let mut q = RealTimeQueue::new(4);
+        for _ in 0..8 {
+            q.enqueue(val.clone());
+        }
+        // Drop in the middle of a rotation, with part of the front copied
+        assert!(q.next_front.is_some() && q.copied > 0);
+        assert_eq!(9, Rc::strong_count(&val));
+        drop(q);
+        assert_eq!(1, Rc::strong_count(&val));
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn max_queue_sliding_window() {
+        let vals = [1, 3, 1, 2, 0, 5, 4, 4, 2, 1, 0, 3];
+        let mut window = MaxQueue::new();
+        assert_eq!(None, window.max());
+        for (i, &val) in vals.iter().enumerate() {
+            window.enqueue(val);
+            if window.len() > 3 {
+                assert_eq!(vals[i - 3], window.dequeue());
+            }
+            let start = i.saturating_sub(2);
+            assert_eq!(vals[start..=i].iter().max(), window.max());
+            assert_eq!(vals[start..=i].iter().max().copied(), window.aggregate());
+            assert_eq!(Some(&vals[start]), window.try_peek());
+        }
+
+        let mut window = MinQueue::new();
+        for val in [4, 2, 3] {
+            window.enqueue(val);
+        }
+        assert_eq!(Some(&2), window.min());
+        window.dequeue();
+        window.dequeue();
+        assert_eq!(Some(&3), window.min());
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn monotonic_queue_sliding_window() {
+        /// Neither `Clone` nor `Copy`.
+        #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+        struct Val(u32);
+
+        let vals = [1, 3, 1, 2, 0, 5, 4, 4, 2, 1, 0, 3];
+        let mut window = MonotonicQueue::new(4, 1);
+        assert_eq!(None, window.min());
+        for (i, &val) in vals.iter().enumerate() {
+            window.enqueue(Val(val));
+            if window.len() > 3 {
+                if let Some(Val(val)) = window.dequeue() {
+                    assert_eq!(vals[i - 3], val);
+                }
+            }
+            let start = i.saturating_sub(2);
+            assert_eq!(vals[start..=i].iter().min(), window.min().map(|val| &val.0));
+            assert!(window.kept_len() <= window.len());
+        }
+
+        // Equal values are each kept, dominated ones are dropped
+        let mut window = MonotonicQueue::default();
+        for val in [2, 1, 1, 3] {
+            window.enqueue(core::cmp::Reverse(val));
+        }
+        assert_eq!(Some(&core::cmp::Reverse(3)), window.min());
+        assert_eq!(1, window.kept_len());
+        assert_eq!(None, window.dequeue());
+        let mut window = MonotonicQueue::default();
+        for val in [2, 1, 1, 3] {
+            window.enqueue(val);
+        }
+        assert_eq!(3, window.kept_len());
+        assert_eq!(None, window.dequeue());
+        assert_eq!(Some(1), window.dequeue());
+        assert_eq!(Some(&1), window.min());
+        assert_eq!(Some(1), window.dequeue());
+        assert_eq!(Some(3), window.dequeue());
+        assert!(window.is_empty());
+        assert_eq!(None, window.min());
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn aggregate_queue_bounded() {
+        let mut q = AggregateQueue::with_stacks(Min, BoundedStack::new(4), BoundedStack::new(4));
+        for val in [3, 1, 2, 5, 4] {
+            assert_eq!(Ok(()), q.try_enqueue(val));
+        }
+        assert_eq!(Err(0), q.try_enqueue(0));
+        for val in [3, 1, 2, 5, 4] {
+            assert_eq!(Some(val), q.try_dequeue());
+        }
+        assert_eq!(None, q.try_dequeue());
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    #[should_panic(expected = "aggregate queue front smaller than its back")]
+    fn aggregate_queue_front_too_small() {
+        AggregateQueue::<u32, _, _>::with_stacks(Min, BoundedStack::new(1), BoundedStack::new(4));
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn aggregate_queue_order() {
+        /// The first and last values, which depends on the order they are combined in.
+        #[derive(Default, Clone)]
+        struct Ends;
+
+        impl Monoid<u32> for Ends {
+            type Output = Option<(u32, u32)>;
+
+            fn empty(&self) -> Self::Output {
+                None
+            }
+
+            fn lift(&self, val: &u32) -> Self::Output {
+                Some((*val, *val))
+            }
+
+            fn combine(&self, lhs: &Self::Output, rhs: &Self::Output) -> Self::Output {
+                match (lhs, rhs) {
+                    (Some((first, _)), Some((_, last))) => Some((*first, *last)),
+                    (ends, None) | (None, ends) => *ends,
+                }
+            }
+        }
+
+        let mut q = AggregateQueue::<u32, Ends>::new();
+        assert_eq!(None, q.aggregate());
+        for val in 0..5 {
+            q.enqueue(val);
+            assert_eq!(Some((0, val)), q.aggregate());
+        }
+        for first in 1..5 {
+            q.dequeue();
+            q.enqueue(first + 4);
+            assert_eq!(Some((first, first + 4)), q.aggregate());
+        }
+        while q.try_dequeue().is_some() {}
+        assert_eq!(None, q.aggregate());
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn queues_checked_ops() {
+        let mut bounded = BoundedQueue::new(1);
+        assert_eq!(None, bounded.try_dequeue());
+        assert_eq!(Ok(()), bounded.try_enqueue(1));
+        assert_eq!(Err(2), bounded.try_enqueue(2));
+        *bounded.peek_mut() += 1;
+        assert_eq!(Some(&2), bounded.try_peek());
+        assert_eq!(Some(2), bounded.try_dequeue());
+        assert_eq!(None, bounded.try_peek_mut());
+
+        let mut linked = LinkedListQueue::new(2, 1);
+        let mut circular = CircularLinkedQueue::new(2, 1);
+        let mut double = DoubleLinkedQueue::new(2, 1);
+        for i in 0..3 {
+            linked.enqueue(i);
+            circular.enqueue(i);
+            double.enqueue(i);
+        }
+        *linked.peek_mut() = 10;
+        *circular.try_peek_mut().unwrap() = 10;
+        *double.peek_mut() = 10;
+        for expected in [10, 1, 2] {
+            assert_eq!(Some(&mut expected.clone()), linked.try_peek_mut());
+            assert_eq!(Some(&expected), circular.try_peek());
+            assert_eq!(Some(expected), linked.try_dequeue());
+            assert_eq!(Some(expected), circular.try_dequeue());
+            assert_eq!(Some(expected), double.try_dequeue());
+        }
+        assert_eq!(None, linked.try_dequeue());
+        assert_eq!(None, circular.try_peek_mut());
+        assert_eq!(None, double.try_dequeue());
+
+        let mut node = Node {
+            next: ptr::null_mut(),
+            val: MaybeUninit::new(1),
+        };
+        let mut intrusive = IntrusiveQueue::new();
+        assert!(intrusive.try_peek().is_none());
+        intrusive.enqueue(&mut node);
+        assert!(intrusive.try_dequeue().is_some());
+        assert!(intrusive.try_dequeue().is_none());
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn queue_trait() {
+        fn rotate<Q>(queue: &mut Q, vals: &[usize]) -> usize
+        where
+            Q: Queue<usize>,
+        {
+            for &val in vals {
+                if queue.try_enqueue(val).is_err() {
+                    break;
+                }
+            }
+            let front = queue.dequeue();
+            queue.enqueue(front);
+            *queue.peek_mut() += 100;
+            let mut rotated = 0;
+            while let Some(val) = queue.try_dequeue() {
+                rotated = rotated * 1000 + val;
+            }
+            rotated
+        }
+
+        let vals = [1, 2, 3];
+        assert_eq!(102_001, rotate(&mut BoundedQueue::new(2), &vals));
+        assert_eq!(102_003_001, rotate(&mut RingQueue::new(1), &vals));
+        assert_eq!(102_003_001, rotate(&mut ShadowCopyQueue::new(1), &vals));
+        assert_eq!(102_003_001, rotate(&mut RealTimeQueue::new(1), &vals));
+        assert_eq!(102_003_001, rotate(&mut LinkedListQueue::new(2, 1), &vals));
+        assert_eq!(
+            102_003_001,
+            rotate(&mut CircularLinkedQueue::new(2, 1), &vals)
+        );
+        assert_eq!(
+            102_003_001,
+            rotate(&mut DoubleLinkedQueue::new(2, 1), &vals)
+        );
+    }
+
+    #[test]
+    fn intrusive_queue_ok() {
+        let mut nodes: [Node<usize>; 4] = core::array::from_fn(|i| Node {
+            next: ptr::null_mut(),
+            val: MaybeUninit::new(i),
+        });
+        let (first, rest) = nodes.split_first_mut().unwrap();
+        let mut q = IntrusiveQueue::new();
+        q.enqueue(first);
+        let first = q.dequeue();
+        for node in rest {
+            q.enqueue(node);
+        }
+        q.enqueue(first);
+        assert_eq!(4, q.len());
+        assert_eq!(1, unsafe { q.peek().val.assume_init() });
+        for i in [1, 2, 3, 0] {
+            let node = q.dequeue();
+            assert!(node.next.is_null());
+            assert_eq!(i, unsafe { node.val.assume_init() });
+        }
+        assert!(q.is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    #[should_panic(expected = "underflow: dequeuing from an empty queue")]
+    fn double_linked_queue_underflow() {
+        let mut q = DoubleLinkedQueue::new(4, 2);
+        q.enqueue(1);
+        q.dequeue();
+        assert!(q.is_empty());
+        q.dequeue();
+    }
+}
@@ -1,8 +1,53 @@
-use alloc::alloc::{alloc, dealloc, handle_alloc_error, Layout};
-use core::mem::MaybeUninit;
-use core::ptr;
+use core::alloc::Layout;
+use core::iter::FusedIterator;
+use core::marker::PhantomData;
+use core::mem::{self, MaybeUninit};
+use core::ptr::{self, NonNull};
+use core::slice;
 
-use crate::allocator::{BlockAllocator, Node};
+use crate::allocator::{
+    AllocError, Allocator, AllocatorStats, BlockAllocator, Global, Nodable, Node, NodePool,
+};
+
+/// Operations shared by all stacks, so that generic code can switch implementations.
+pub trait Stack<T> {
+    /// Why [`try_push`](Stack::try_push) failed: the value itself for bounded stacks, an
+    /// [`AllocError`] for the others.
+    type Error;
+
+    fn is_empty(&self) -> bool;
+
+    fn len(&self) -> usize;
+
+    /// The most values the stack can hold, `None` if only memory limits it.
+    fn max_len(&self) -> Option<usize>;
+
+    fn push(&mut self, val: T);
+
+    fn try_push(&mut self, val: T) -> Result<(), Self::Error>;
+
+    /// Same as [`try_push`](Stack::try_push), but `val` is given back whatever the failure,
+    /// so that it can be put elsewhere.
+    fn try_push_or_return(&mut self, val: T) -> Result<(), T>;
+
+    fn pop(&mut self) -> T;
+
+    fn try_pop(&mut self) -> Option<T> {
+        (!self.is_empty()).then(|| self.pop())
+    }
+
+    fn peek(&self) -> &T;
+
+    fn try_peek(&self) -> Option<&T> {
+        (!self.is_empty()).then(|| self.peek())
+    }
+
+    fn peek_mut(&mut self) -> &mut T;
+
+    fn try_peek_mut(&mut self) -> Option<&mut T> {
+        (!self.is_empty()).then(|| self.peek_mut())
+    }
+}
 
 #[derive(Debug)]
 pub struct ArrayStack<T, const N: usize> {
@@ -45,6 +90,15 @@ impl<T, const N: usize> ArrayStack<T, N> {
         self.len += 1;
     }
 
+    /// Gives `val` back if the stack is full.
+    pub fn try_push(&mut self, val: T) -> Result<(), T> {
+        if self.len == self.stack.len() {
+            return Err(val);
+        }
+        self.push(val);
+        Ok(())
+    }
+
     pub fn pop(&mut self) -> T {
         assert!(!self.is_empty(), "underflow: popping from an empty stack");
         self.len -= 1;
@@ -56,6 +110,42 @@ impl<T, const N: usize> ArrayStack<T, N> {
         let peek = self.len - 1;
         unsafe { self.stack[peek].assume_init_ref() }
     }
+
+    pub fn peek_mut(&mut self) -> &mut T {
+        assert!(!self.is_empty(), "underflow: peeking at an empty stack");
+        let peek = self.len - 1;
+        unsafe { self.stack[peek].assume_init_mut() }
+    }
+
+    pub fn try_pop(&mut self) -> Option<T> {
+        (!self.is_empty()).then(|| self.pop())
+    }
+
+    pub fn try_peek(&self) -> Option<&T> {
+        (!self.is_empty()).then(|| self.peek())
+    }
+
+    pub fn try_peek_mut(&mut self) -> Option<&mut T> {
+        (!self.is_empty()).then(|| self.peek_mut())
+    }
+
+    pub fn iter(&self) -> StackIter<'_, T> {
+        let top = (self.stack.as_ptr() as *mut T, self.len);
+        unsafe { StackIter::new(top, Segments::Done, self.len) }
+    }
+
+    pub fn iter_mut(&mut self) -> StackIterMut<'_, T> {
+        let top = (self.stack.as_mut_ptr() as *mut T, self.len);
+        unsafe { StackIterMut::new(top, Segments::Done, self.len) }
+    }
+
+    pub fn drain(&mut self) -> StackDrain<'_, T, Self> {
+        StackDrain {
+            len: self.len(),
+            stack: self,
+            pop: Self::pop,
+        }
+    }
 }
 
 impl<T, const N: usize> Drop for ArrayStack<T, N> {
@@ -67,27 +157,42 @@ impl<T, const N: usize> Drop for ArrayStack<T, N> {
 }
 
 #[derive(Debug)]
-pub struct BoundedStack<T> {
+pub struct BoundedStack<T, A = Global>
+where
+    A: Allocator,
+{
     base: *mut T,
     top: *mut T,
     max_size: usize,
+    alloc: A,
 }
 
+#[cfg(feature = "alloc")]
 impl<T> BoundedStack<T> {
     pub fn new(max_size: usize) -> Self {
-        let layout = Layout::array::<T>(max_size).expect("Couldn't create memory layout");
-        let base = unsafe { alloc(layout) };
-        if base.is_null() {
-            handle_alloc_error(layout);
-        }
-        let base = base as *mut _;
+        Self::new_in(max_size, Global)
+    }
+}
+
+impl<T, A> BoundedStack<T, A>
+where
+    A: Allocator,
+{
+    pub fn new_in(max_size: usize, alloc: A) -> Self {
+        Self::try_new_in(max_size, alloc).unwrap_or_else(|err| err.handle())
+    }
+
+    pub fn try_new_in(max_size: usize, alloc: A) -> Result<Self, AllocError> {
+        let layout = Layout::array::<T>(max_size).map_err(|_| AllocError::CapacityOverflow)?;
+        let base = alloc.allocate(layout)?.as_ptr() as *mut _;
         let top = base;
 
-        Self {
+        Ok(Self {
             base,
             top,
             max_size,
-        }
+            alloc,
+        })
     }
 
     pub fn is_empty(&self) -> bool {
@@ -113,6 +218,15 @@ impl<T> BoundedStack<T> {
         }
     }
 
+    /// Gives `val` back if the stack is full.
+    pub fn try_push(&mut self, val: T) -> Result<(), T> {
+        if self.len() == self.max_size {
+            return Err(val);
+        }
+        self.push(val);
+        Ok(())
+    }
+
     pub fn pop(&mut self) -> T {
         unsafe {
             assert!(!self.is_empty(), "underflow: popping from an empty stack");
@@ -128,25 +242,86 @@ impl<T> BoundedStack<T> {
             &*peek
         }
     }
+
+    pub fn peek_mut(&mut self) -> &mut T {
+        assert!(!self.is_empty(), "underflow: peeking at an empty stack");
+        unsafe {
+            let peek = self.top.offset(-1);
+            &mut *peek
+        }
+    }
+
+    pub fn try_pop(&mut self) -> Option<T> {
+        (!self.is_empty()).then(|| self.pop())
+    }
+
+    pub fn try_peek(&self) -> Option<&T> {
+        (!self.is_empty()).then(|| self.peek())
+    }
+
+    pub fn try_peek_mut(&mut self) -> Option<&mut T> {
+        (!self.is_empty()).then(|| self.peek_mut())
+    }
+
+    pub fn iter(&self) -> StackIter<'_, T> {
+        let len = self.len();
+        unsafe { StackIter::new((self.base, len), Segments::Done, len) }
+    }
+
+    pub fn iter_mut(&mut self) -> StackIterMut<'_, T> {
+        let len = self.len();
+        unsafe { StackIterMut::new((self.base, len), Segments::Done, len) }
+    }
+
+    pub fn drain(&mut self) -> StackDrain<'_, T, Self> {
+        StackDrain {
+            len: self.len(),
+            stack: self,
+            pop: Self::pop,
+        }
+    }
+
+    /// The bottom of the stack, values are laid out from there up to its top.
+    pub(crate) fn as_ptr(&self) -> *const T {
+        self.base
+    }
+
+    /// # Safety
+    ///
+    /// Values above `len` must have been moved out, `len` must not exceed the current
+    /// length.
+    pub(crate) unsafe fn set_len(&mut self, len: usize) {
+        self.top = unsafe { self.base.add(len) };
+    }
 }
 
-impl<T> Drop for BoundedStack<T> {
+impl<T, A> Drop for BoundedStack<T, A>
+where
+    A: Allocator,
+{
     fn drop(&mut self) {
         while !self.is_empty() {
             self.pop();
         }
         let layout = Layout::array::<T>(self.max_size).unwrap();
-        unsafe { dealloc(self.base as *mut u8, layout) };
+        unsafe {
+            self.alloc
+                .deallocate(NonNull::new_unchecked(self.base as *mut u8), layout)
+        };
     }
 }
 
 #[derive(Debug)]
-pub struct LinkedListStack<T> {
-    allocator: BlockAllocator<Node<T>>,
+pub struct LinkedListStack<T, P = BlockAllocator<Node<T>>>
+where
+    P: NodePool<Node<T>>,
+{
+    allocator: P,
     len: usize,
     head: *mut Node<T>,
 }
 
+#[cfg(feature = "alloc")]
 impl<T> Default for LinkedListStack<T> {
     fn default() -> Self {
         Self::new(
@@ -156,10 +331,29 @@ impl<T> Default for LinkedListStack<T> {
     }
 }
 
+#[cfg(feature = "alloc")]
 impl<T> LinkedListStack<T> {
     pub fn new(block_size: usize, blocks_cap: usize) -> Self {
+        Self::new_in(block_size, blocks_cap, Global)
+    }
+}
+
+impl<T, A> LinkedListStack<T, BlockAllocator<Node<T>, A>>
+where
+    A: Allocator,
+{
+    pub fn new_in(block_size: usize, blocks_cap: usize, alloc: A) -> Self {
+        Self::with_allocator(BlockAllocator::new_in(block_size, blocks_cap, alloc))
+    }
+}
+
+impl<T, P> LinkedListStack<T, P>
+where
+    P: NodePool<Node<T>>,
+{
+    pub fn with_allocator(allocator: P) -> Self {
         Self {
-            allocator: BlockAllocator::new(block_size, blocks_cap),
+            allocator,
             len: 0,
             head: ptr::null_mut(),
         }
@@ -174,13 +368,38 @@ impl<T> LinkedListStack<T> {
     }
 
     pub fn push(&mut self, val: T) {
-        let tmp = self.allocator.get_node();
+        self.try_push(val).unwrap_or_else(|err| err.handle())
+    }
+
+    pub fn try_push(&mut self, val: T) -> Result<(), AllocError> {
+        self.push_or_return(val).map_err(|(_, err)| err)
+    }
+
+    /// Same as [`try_push`](Self::try_push), but gives `val` back along with the error.
+    fn push_or_return(&mut self, val: T) -> Result<(), (T, AllocError)> {
+        let tmp = match self.allocator.try_get_node() {
+            Ok(tmp) => tmp,
+            Err(err) => return Err((val, err)),
+        };
         unsafe {
             (*tmp).val = MaybeUninit::new(val);
             (*tmp).next = self.head;
         }
         self.head = tmp;
         self.len += 1;
+        Ok(())
+    }
+
+    pub fn try_reserve(&mut self, additional: usize) -> Result<(), AllocError> {
+        self.allocator.try_reserve(additional)
+    }
+
+    pub fn shrink_to_fit(&mut self) {
+        self.allocator.shrink_to_fit();
+    }
+
+    pub fn allocator_stats(&self) -> AllocatorStats {
+        self.allocator.stats()
     }
 
     pub fn pop(&mut self) -> T {
@@ -199,52 +418,224 @@ impl<T> LinkedListStack<T> {
         assert!(!self.is_empty(), "underflow: peeking at an empty stack");
         unsafe { (*self.head).val.assume_init_ref() }
     }
+
+    pub fn peek_mut(&mut self) -> &mut T {
+        assert!(!self.is_empty(), "underflow: peeking at an empty stack");
+        unsafe { (*self.head).val.assume_init_mut() }
+    }
+
+    pub fn try_pop(&mut self) -> Option<T> {
+        (!self.is_empty()).then(|| self.pop())
+    }
+
+    pub fn try_peek(&self) -> Option<&T> {
+        (!self.is_empty()).then(|| self.peek())
+    }
+
+    pub fn try_peek_mut(&mut self) -> Option<&mut T> {
+        (!self.is_empty()).then(|| self.peek_mut())
+    }
+
+    pub fn iter(&self) -> StackIter<'_, T> {
+        let top = (NonNull::dangling().as_ptr(), 0);
+        unsafe { StackIter::new(top, Segments::Nodes(self.head), self.len) }
+    }
+
+    pub fn iter_mut(&mut self) -> StackIterMut<'_, T> {
+        let top = (NonNull::dangling().as_ptr(), 0);
+        unsafe { StackIterMut::new(top, Segments::Nodes(self.head), self.len) }
+    }
+
+    pub fn drain(&mut self) -> StackDrain<'_, T, Self> {
+        StackDrain {
+            len: self.len(),
+            stack: self,
+            pop: Self::pop,
+        }
+    }
+
+    /// Marks the current height, to get back to it with [`rollback_to`](Self::rollback_to).
+    pub fn checkpoint(&self) -> Mark {
+        Mark { len: self.len }
+    }
+
+    /// Pops and drops values down to the height of `mark`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the stack is already below `mark`.
+    pub fn rollback_to(&mut self, mark: Mark) {
+        drop(self.drain_to(mark));
+    }
+
+    /// Pops values down to the height of `mark`, from the top.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the stack is already below `mark`.
+    pub fn drain_to(&mut self, mark: Mark) -> StackDrain<'_, T, Self> {
+        assert!(
+            mark.len <= self.len,
+            "rollback: the stack is below the mark"
+        );
+        StackDrain {
+            len: self.len - mark.len,
+            stack: self,
+            pop: Self::pop,
+        }
+    }
 }
 
-impl<T> Drop for LinkedListStack<T> {
+impl<T, P> Drop for LinkedListStack<T, P>
+where
+    P: NodePool<Node<T>>,
+{
     fn drop(&mut self) {
+        let mut tail = ptr::null_mut();
         let mut next = self.head;
         while !next.is_null() {
-            let tmp = next;
+            tail = next;
             unsafe {
-                (*tmp).val.assume_init_drop();
-                next = (*tmp).next;
-                self.allocator.return_node(tmp);
+                (*tail).val.assume_init_drop();
+                next = (*tail).next;
             }
         }
+        unsafe { self.allocator.return_chain(self.head, tail, self.len) };
+    }
+}
+
+/// A stack linking nodes owned by the caller through [`Nodable::next_mut`], without
+/// allocating.
+///
+/// Nodes are exclusively borrowed while they are on the stack and handed back by
+/// [`pop`](Self::pop) with their link cleared.
+#[derive(Debug)]
+pub struct IntrusiveStack<'a, N>
+where
+    N: Nodable,
+{
+    len: usize,
+    head: *mut N,
+    nodes: PhantomData<&'a mut N>,
+}
+
+impl<N> Default for IntrusiveStack<'_, N>
+where
+    N: Nodable,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, N> IntrusiveStack<'a, N>
+where
+    N: Nodable,
+{
+    pub fn new() -> Self {
+        Self {
+            len: 0,
+            head: ptr::null_mut(),
+            nodes: PhantomData,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.head.is_null()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn push(&mut self, node: &'a mut N) {
+        *node.next_mut() = self.head;
+        self.head = node;
+        self.len += 1;
+    }
+
+    pub fn pop(&mut self) -> &'a mut N {
+        assert!(!self.is_empty(), "underflow: popping from an empty stack");
+        let node = unsafe { &mut *self.head };
+        self.head = mem::replace(node.next_mut(), ptr::null_mut());
+        self.len -= 1;
+        node
+    }
+
+    pub fn peek(&self) -> &N {
+        assert!(!self.is_empty(), "underflow: peeking at an empty stack");
+        unsafe { &*self.head }
+    }
+
+    pub fn try_pop(&mut self) -> Option<&'a mut N> {
+        (!self.is_empty()).then(|| self.pop())
+    }
+
+    pub fn try_peek(&self) -> Option<&N> {
+        (!self.is_empty()).then(|| self.peek())
     }
 }
 
 #[derive(Debug)]
-pub struct UnboundedStack<T> {
+struct Chunk<T> {
+    base: *mut T,
+    top: *mut T,
+    previous: *mut Chunk<T>,
+}
+
+/// A stack of fixed-size chunks, allocated as it grows.
+///
+/// The last chunk emptied is kept as a spare until the one below it empties too, so that
+/// pushing and popping around a chunk boundary does not allocate each time.
+#[derive(Debug)]
+pub struct UnboundedStack<T, A = Global>
+where
+    A: Allocator,
+{
     base: *mut T,
     top: *mut T,
     chunk_size: usize,
-    previous: *mut UnboundedStack<T>,
+    previous: *mut Chunk<T>,
+    /// An empty chunk, whose `base` is ready to be pushed to.
+    spare: *mut Chunk<T>,
     len: usize,
+    alloc: A,
 }
 
+#[cfg(feature = "alloc")]
 impl<T> UnboundedStack<T> {
     pub fn new(chunk_size: usize) -> Self {
-        let chunk_layout = Layout::array::<T>(chunk_size).expect("Couldn't create memory layout");
-        let base = unsafe { alloc(chunk_layout) };
-        if base.is_null() {
-            handle_alloc_error(chunk_layout);
-        }
-        let base = base as *mut _;
+        Self::new_in(chunk_size, Global)
+    }
+}
+
+impl<T, A> UnboundedStack<T, A>
+where
+    A: Allocator,
+{
+    pub fn new_in(chunk_size: usize, alloc: A) -> Self {
+        Self::try_new_in(chunk_size, alloc).unwrap_or_else(|err| err.handle())
+    }
+
+    pub fn try_new_in(chunk_size: usize, alloc: A) -> Result<Self, AllocError> {
+        let chunk_layout =
+            Layout::array::<T>(chunk_size).map_err(|_| AllocError::CapacityOverflow)?;
+        let base = alloc.allocate(chunk_layout)?.as_ptr() as *mut _;
         let top = base;
 
-        Self {
+        Ok(Self {
             base,
             top,
             chunk_size,
             previous: ptr::null_mut(),
+            spare: ptr::null_mut(),
             len: 0,
-        }
+            alloc,
+        })
     }
 
     pub fn is_empty(&self) -> bool {
-        self.base == self.top && self.previous.is_null()
+        self.len == 0
     }
 
     pub fn len(&self) -> usize {
@@ -252,26 +643,33 @@ impl<T> UnboundedStack<T> {
     }
 
     pub fn push(&mut self, val: T) {
-        if self.top == unsafe { self.base.add(self.chunk_size) } {
-            let node_layout = Layout::new::<UnboundedStack<T>>();
-            let new_node = unsafe { alloc(node_layout) };
-            if new_node.is_null() {
-                handle_alloc_error(node_layout);
-            }
-            let new_node = new_node as *mut UnboundedStack<T>;
-            unsafe {
-                (*new_node).base = self.base;
-                (*new_node).top = self.top;
-                (*new_node).chunk_size = self.chunk_size;
-                (*new_node).previous = self.previous;
-            }
+        self.try_push(val).unwrap_or_else(|err| err.handle())
+    }
 
-            let chunk_layout = Layout::array::<T>(self.chunk_size).unwrap();
-            let new_chunk = unsafe { alloc(chunk_layout) };
-            if new_chunk.is_null() {
-                handle_alloc_error(chunk_layout);
-            }
-            let new_chunk = new_chunk as *mut _;
+    pub fn try_push(&mut self, val: T) -> Result<(), AllocError> {
+        self.push_or_return(val).map_err(|(_, err)| err)
+    }
+
+    /// Same as [`try_push`](Self::try_push), but gives `val` back along with the error.
+    fn push_or_return(&mut self, val: T) -> Result<(), (T, AllocError)> {
+        // Zero-sized values never fill a chunk
+        if mem::size_of::<T>() != 0 && self.top == unsafe { self.base.add(self.chunk_size) } {
+            let (new_node, new_chunk) = if self.spare.is_null() {
+                match self.alloc_chunk() {
+                    Ok(chunk) => chunk,
+                    Err(err) => return Err((val, err)),
+                }
+            } else {
+                let spare = mem::replace(&mut self.spare, ptr::null_mut());
+                (spare, unsafe { (*spare).base })
+            };
+            unsafe {
+                new_node.write(Chunk {
+                    base: self.base,
+                    top: self.top,
+                    previous: self.previous,
+                })
+            };
 
             self.previous = new_node;
             self.base = new_chunk;
@@ -282,21 +680,25 @@ impl<T> UnboundedStack<T> {
             self.top = self.top.add(1);
             self.len += 1;
         }
+        Ok(())
     }
 
     pub fn pop(&mut self) -> T {
         assert!(!self.is_empty(), "underflow: popping from an empty stack");
-        if self.base == self.top {
+        if self.top_len() == 0 {
+            // The emptied chunk becomes the spare, replacing the one above it
             unsafe {
-                let chunk_layout = Layout::array::<T>(self.chunk_size).unwrap();
-                dealloc(self.base as *mut u8, chunk_layout);
+                self.free_spare();
                 let old_node = self.previous;
                 self.previous = (*old_node).previous;
-                self.base = (*old_node).base;
+                let empty = mem::replace(&mut self.base, (*old_node).base);
                 self.top = (*old_node).top;
-                self.chunk_size = (*old_node).chunk_size;
-                let node_layout = Layout::new::<UnboundedStack<T>>();
-                dealloc(old_node as *mut u8, node_layout);
+                old_node.write(Chunk {
+                    base: empty,
+                    top: empty,
+                    previous: ptr::null_mut(),
+                });
+                self.spare = old_node;
             }
         }
         unsafe {
@@ -308,49 +710,201 @@ impl<T> UnboundedStack<T> {
 
     pub fn peek(&self) -> &T {
         assert!(!self.is_empty(), "underflow: peeking at an empty stack");
-        if self.base == self.top {
+        if self.top_len() == 0 {
             unsafe { &*(*self.previous).top.offset(-1) }
         } else {
             unsafe { &*self.top.offset(-1) }
         }
     }
+
+    pub fn peek_mut(&mut self) -> &mut T {
+        assert!(!self.is_empty(), "underflow: peeking at an empty stack");
+        if self.top_len() == 0 {
+            unsafe { &mut *(*self.previous).top.offset(-1) }
+        } else {
+            unsafe { &mut *self.top.offset(-1) }
+        }
+    }
+
+    pub fn try_pop(&mut self) -> Option<T> {
+        (!self.is_empty()).then(|| self.pop())
+    }
+
+    pub fn try_peek(&self) -> Option<&T> {
+        (!self.is_empty()).then(|| self.peek())
+    }
+
+    pub fn try_peek_mut(&mut self) -> Option<&mut T> {
+        (!self.is_empty()).then(|| self.peek_mut())
+    }
+
+    /// Frees the spare chunk, if any.
+    pub fn shrink_to_fit(&mut self) {
+        self.free_spare();
+    }
+
+    pub fn iter(&self) -> StackIter<'_, T> {
+        unsafe {
+            let top = (self.base, self.top_len());
+            StackIter::new(top, Segments::Chunks(self.previous), self.len)
+        }
+    }
+
+    pub fn iter_mut(&mut self) -> StackIterMut<'_, T> {
+        unsafe {
+            let top = (self.base, self.top_len());
+            StackIterMut::new(top, Segments::Chunks(self.previous), self.len)
+        }
+    }
+
+    /// Number of values in the top chunk, which holds all of them if they are zero-sized.
+    fn top_len(&self) -> usize {
+        if mem::size_of::<T>() == 0 {
+            self.len
+        } else {
+            unsafe { self.top.offset_from(self.base) as usize }
+        }
+    }
+
+    pub fn drain(&mut self) -> StackDrain<'_, T, Self> {
+        StackDrain {
+            len: self.len(),
+            stack: self,
+            pop: Self::pop,
+        }
+    }
+
+    /// Marks the current height, to get back to it with [`rollback_to`](Self::rollback_to).
+    pub fn checkpoint(&self) -> Mark {
+        Mark { len: self.len }
+    }
+
+    /// Pops and drops values down to the height of `mark`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the stack is already below `mark`.
+    pub fn rollback_to(&mut self, mark: Mark) {
+        drop(self.drain_to(mark));
+    }
+
+    /// Pops values down to the height of `mark`, from the top.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the stack is already below `mark`.
+    pub fn drain_to(&mut self, mark: Mark) -> StackDrain<'_, T, Self> {
+        assert!(
+            mark.len <= self.len,
+            "rollback: the stack is below the mark"
+        );
+        StackDrain {
+            len: self.len - mark.len,
+            stack: self,
+            pop: Self::pop,
+        }
+    }
+
+    /// Allocates a chunk along with the node that links it once it is full.
+    fn alloc_chunk(&mut self) -> Result<(*mut Chunk<T>, *mut T), AllocError> {
+        let node_layout = Layout::new::<Chunk<T>>();
+        let new_node = self.alloc.allocate(node_layout)?;
+
+        let chunk_layout = Layout::array::<T>(self.chunk_size).unwrap();
+        match self.alloc.allocate(chunk_layout) {
+            Ok(new_chunk) => Ok((new_node.as_ptr() as *mut _, new_chunk.as_ptr() as *mut _)),
+            Err(err) => {
+                unsafe { self.alloc.deallocate(new_node, node_layout) };
+                Err(err)
+            }
+        }
+    }
+
+    fn free_spare(&mut self) {
+        if self.spare.is_null() {
+            return;
+        }
+        let spare = mem::replace(&mut self.spare, ptr::null_mut());
+        unsafe {
+            let chunk_layout = Layout::array::<T>(self.chunk_size).unwrap();
+            self.alloc.deallocate(
+                NonNull::new_unchecked((*spare).base as *mut u8),
+                chunk_layout,
+            );
+            let node_layout = Layout::new::<Chunk<T>>();
+            self.alloc
+                .deallocate(NonNull::new_unchecked(spare as *mut u8), node_layout);
+        }
+    }
 }
 
-impl<T> Drop for UnboundedStack<T> {
+impl<T, A> Drop for UnboundedStack<T, A>
+where
+    A: Allocator,
+{
     fn drop(&mut self) {
         while !self.is_empty() {
             self.pop();
         }
+        self.free_spare();
         let chunk_layout = Layout::array::<T>(self.chunk_size).unwrap();
-        unsafe { dealloc(self.base as *mut u8, chunk_layout) };
+        unsafe {
+            self.alloc
+                .deallocate(NonNull::new_unchecked(self.base as *mut u8), chunk_layout)
+        };
     }
 }
 
+/// A stack whose array is resized without ever copying it all at once.
+///
+/// Once 3/4 full, an array twice as large is allocated, and once less than 1/4 full, an
+/// array half as large (but never smaller than the initial one). Values are then copied to
+/// it a few at a time on each operation, so every operation takes worst-case O(1) time.
 #[derive(Debug)]
-pub struct ShadowCopyStack<T> {
+pub struct ShadowCopyStack<T, A = Global>
+where
+    A: Allocator,
+{
     base: *mut T,
     base_size: usize,
     max_size: usize,
+    min_size: usize,
     copy: *mut T,
     copy_size: usize,
+    copy_max_size: usize,
+    alloc: A,
 }
 
+#[cfg(feature = "alloc")]
 impl<T> ShadowCopyStack<T> {
     pub fn new(base_size: usize) -> Self {
-        let base_layout = Layout::array::<T>(base_size).expect("Couldn't create memory layout");
-        let base = unsafe { alloc(base_layout) };
-        if base.is_null() {
-            handle_alloc_error(base_layout);
-        }
-        let base = base as *mut _;
+        Self::new_in(base_size, Global)
+    }
+}
 
-        Self {
+impl<T, A> ShadowCopyStack<T, A>
+where
+    A: Allocator,
+{
+    pub fn new_in(base_size: usize, alloc: A) -> Self {
+        Self::try_new_in(base_size, alloc).unwrap_or_else(|err| err.handle())
+    }
+
+    pub fn try_new_in(base_size: usize, alloc: A) -> Result<Self, AllocError> {
+        let base_layout =
+            Layout::array::<T>(base_size).map_err(|_| AllocError::CapacityOverflow)?;
+        let base = alloc.allocate(base_layout)?.as_ptr() as *mut _;
+
+        Ok(Self {
             base,
             base_size: 0,
             max_size: base_size,
+            min_size: base_size,
             copy: ptr::null_mut(),
             copy_size: 0,
-        }
+            copy_max_size: 0,
+            alloc,
+        })
     }
 
     pub fn is_empty(&self) -> bool {
@@ -362,52 +916,66 @@ impl<T> ShadowCopyStack<T> {
     }
 
     pub fn push(&mut self, val: T) {
+        self.try_push(val).unwrap_or_else(|err| err.handle())
+    }
+
+    pub fn try_push(&mut self, val: T) -> Result<(), AllocError> {
+        self.push_or_return(val).map_err(|(_, err)| err)
+    }
+
+    /// Same as [`try_push`](Self::try_push), but gives `val` back along with the error.
+    fn push_or_return(&mut self, val: T) -> Result<(), (T, AllocError)> {
+        if let Err(err) = self.start_growing_copy() {
+            return Err((val, err));
+        }
+        // A halving copy completes long before the values outgrow it
+        debug_assert!(self.copy.is_null() || self.base_size < self.copy_max_size);
         unsafe { ptr::write(self.base.add(self.base_size), val) };
         self.base_size += 1;
-        if !self.copy.is_null() || self.base_size as f32 >= 0.75 * self.max_size as f32 {
-            if self.copy.is_null() {
-                let copy_layout =
-                    Layout::array::<T>(2 * self.max_size).expect("Couldn't create memory layout");
-                let copy = unsafe { alloc(copy_layout) };
-                if copy.is_null() {
-                    handle_alloc_error(copy_layout);
+        self.copy_step();
+        Ok(())
+    }
+
+    /// Allocates the copy twice as large once a push would make the stack 3/4 full.
+    fn start_growing_copy(&mut self) -> Result<(), AllocError> {
+        // Integer math, a rounded 3/4 could leave the copy no time to complete
+        if self.copy.is_null() && self.base_size + 1 >= self.max_size - self.max_size / 4 {
+            let copy_max_size = self
+                .max_size
+                .checked_mul(2)
+                .ok_or(AllocError::CapacityOverflow)?;
+            let copy_layout =
+                Layout::array::<T>(copy_max_size).map_err(|_| AllocError::CapacityOverflow)?;
+            self.copy = self.alloc.allocate(copy_layout)?.as_ptr() as *mut _;
+            self.copy_max_size = copy_max_size;
+        }
+        Ok(())
+    }
+
+    pub fn pop(&mut self) -> T {
+        assert!(!self.is_empty(), "underflow: popping from an empty stack");
+        // Shrinking is best effort, the stack is only kept larger if it fails
+        if self.copy.is_null() && self.base_size - 1 < self.max_size / 4 {
+            let copy_max_size = self.max_size / 2;
+            if copy_max_size >= self.min_size {
+                let copy_layout = Layout::array::<T>(copy_max_size).unwrap();
+                if let Ok(copy) = self.alloc.allocate(copy_layout) {
+                    self.copy = copy.as_ptr() as *mut _;
+                    self.copy_max_size = copy_max_size;
                 }
-                self.copy = copy as *mut _;
-            }
-            let mut additional_copies = 4;
-            while additional_copies > 0 && self.copy_size < self.base_size {
-                unsafe {
-                    self.base
-                        .add(self.copy_size)
-                        .copy_to(self.copy.add(self.copy_size), 1)
-                };
-                self.copy_size += 1;
-                additional_copies -= 1;
-            }
-            // Copy complete
-            if self.copy_size == self.base_size {
-                let base_layout = Layout::array::<T>(self.max_size).unwrap();
-                unsafe { dealloc(self.base as *mut u8, base_layout) };
-                self.base = self.copy;
-                self.max_size *= 2;
-                self.copy = ptr::null_mut();
-                self.copy_size = 0;
             }
         }
+        self.pop_no_shrink()
     }
 
-    pub fn pop(&mut self) -> T {
+    /// Same as [`pop`](Self::pop), but never starts a halving copy, so that it never
+    /// allocates.
+    pub(crate) fn pop_no_shrink(&mut self) -> T {
         assert!(!self.is_empty(), "underflow: popping from an empty stack");
         self.base_size -= 1;
         let val = unsafe { ptr::read(self.base.add(self.base_size)) };
-        // Copy complete
-        if self.base_size > 0 && self.copy_size == self.base_size {
-            let base_layout = Layout::array::<T>(self.max_size).unwrap();
-            unsafe { dealloc(self.base as *mut u8, base_layout) };
-            self.base = self.copy;
-            self.max_size *= 2;
-            self.copy = ptr::null_mut();
-            self.copy_size = 0;
+        if self.base_size > 0 {
+            self.copy_step();
         }
         val
     }
@@ -416,20 +984,809 @@ impl<T> ShadowCopyStack<T> {
         assert!(!self.is_empty(), "underflow: peeking at an empty stack");
         unsafe { &*self.base.add(self.base_size - 1) }
     }
-}
 
-impl<T> Drop for ShadowCopyStack<T> {
-    fn drop(&mut self) {
-        while !self.is_empty() {
-            self.base_size -= 1;
-            unsafe { ptr::drop_in_place(self.base.add(self.base_size)) };
-        }
-        let base_layout = Layout::array::<T>(self.max_size).unwrap();
-        unsafe { dealloc(self.base as *mut u8, base_layout) };
+    pub fn peek_mut(&mut self) -> &mut T {
+        assert!(!self.is_empty(), "underflow: peeking at an empty stack");
+        unsafe { &mut *self.base.add(self.base_size - 1) }
+    }
+
+    pub fn try_pop(&mut self) -> Option<T> {
+        (!self.is_empty()).then(|| self.pop())
+    }
+
+    pub fn try_peek(&self) -> Option<&T> {
+        (!self.is_empty()).then(|| self.peek())
+    }
+
+    pub fn try_peek_mut(&mut self) -> Option<&mut T> {
+        (!self.is_empty()).then(|| self.peek_mut())
+    }
+
+    pub fn iter(&self) -> StackIter<'_, T> {
+        let (top, rest) = self.segments();
+        unsafe { StackIter::new(top, rest, self.base_size) }
+    }
+
+    pub fn iter_mut(&mut self) -> StackIterMut<'_, T> {
+        let (top, rest) = self.segments();
+        unsafe { StackIterMut::new(top, rest, self.base_size) }
+    }
+
+    pub fn drain(&mut self) -> StackDrain<'_, T, Self> {
+        StackDrain {
+            len: self.len(),
+            stack: self,
+            pop: Self::pop,
+        }
+    }
+
+    /// Values already copied live in the copy, which is the one kept once it completes,
+    /// so that they can be mutated in place.
+    fn segments(&self) -> ((*mut T, usize), Segments<T>) {
+        let top = unsafe { self.base.add(self.copy_size) };
+        let rest = match self.copy_size {
+            0 => Segments::Done,
+            copy_size => Segments::Slice(self.copy, copy_size),
+        };
+        ((top, self.base_size - self.copy_size), rest)
+    }
+
+    /// Copies a few more values if a copy is in progress, and swaps it in once complete.
+    fn copy_step(&mut self) {
+        if self.copy.is_null() {
+            return;
+        }
+        let mut additional_copies = 4;
+        while additional_copies > 0 && self.copy_size < self.base_size {
+            unsafe {
+                self.base
+                    .add(self.copy_size)
+                    .copy_to(self.copy.add(self.copy_size), 1)
+            };
+            self.copy_size += 1;
+            additional_copies -= 1;
+        }
+        // Copy complete
+        if self.copy_size == self.base_size {
+            self.swap_copy();
+        }
+    }
+
+    fn swap_copy(&mut self) {
+        let base_layout = Layout::array::<T>(self.max_size).unwrap();
+        unsafe {
+            self.alloc
+                .deallocate(NonNull::new_unchecked(self.base as *mut u8), base_layout)
+        };
+        self.base = self.copy;
+        self.max_size = self.copy_max_size;
+        self.copy = ptr::null_mut();
+        self.copy_size = 0;
+        self.copy_max_size = 0;
+    }
+}
+
+impl<T, A> Drop for ShadowCopyStack<T, A>
+where
+    A: Allocator,
+{
+    fn drop(&mut self) {
+        self.iter_mut()
+            .for_each(|val| unsafe { ptr::drop_in_place(val) });
+        let base_layout = Layout::array::<T>(self.max_size).unwrap();
+        unsafe {
+            self.alloc
+                .deallocate(NonNull::new_unchecked(self.base as *mut u8), base_layout)
+        };
         if !self.copy.is_null() {
-            let copy_layout = Layout::array::<T>(2 * self.max_size).unwrap();
-            unsafe { dealloc(self.copy as *mut u8, copy_layout) };
+            let copy_layout = Layout::array::<T>(self.copy_max_size).unwrap();
+            unsafe {
+                self.alloc
+                    .deallocate(NonNull::new_unchecked(self.copy as *mut u8), copy_layout)
+            };
+        }
+    }
+}
+
+/// What a stack iterator walks once its current segment is exhausted, from the top of the
+/// stack to its bottom.
+#[derive(Debug)]
+enum Segments<T> {
+    Done,
+    Slice(*mut T, usize),
+    Chunks(*mut Chunk<T>),
+    Nodes(*mut Node<T>),
+}
+
+impl<T> Segments<T> {
+    /// Start and length of the next segment, whose values are in bottom-to-top order.
+    unsafe fn next_segment(&mut self) -> Option<(*mut T, usize)> {
+        match *self {
+            Self::Done => None,
+            Self::Slice(base, len) => {
+                *self = Self::Done;
+                Some((base, len))
+            }
+            Self::Chunks(chunk) if !chunk.is_null() => unsafe {
+                *self = Self::Chunks((*chunk).previous);
+                let len = (*chunk).top.offset_from((*chunk).base) as usize;
+                Some(((*chunk).base, len))
+            },
+            Self::Nodes(node) if !node.is_null() => unsafe {
+                *self = Self::Nodes((*node).next);
+                Some((ptr::addr_of_mut!((*node).val) as *mut T, 1))
+            },
+            Self::Chunks(_) | Self::Nodes(_) => None,
+        }
+    }
+}
+
+/// Iterates from the top of a stack to its bottom.
+#[derive(Debug)]
+pub struct StackIter<'a, T> {
+    segment: slice::Iter<'a, T>,
+    rest: Segments<T>,
+    len: usize,
+}
+
+impl<'a, T> StackIter<'a, T> {
+    /// # Safety
+    ///
+    /// `top` and `rest` must hold `len` values borrowed for `'a`.
+    unsafe fn new(top: (*mut T, usize), rest: Segments<T>, len: usize) -> Self {
+        Self {
+            segment: unsafe { slice::from_raw_parts(top.0, top.1) }.iter(),
+            rest,
+            len,
+        }
+    }
+}
+
+impl<'a, T> Iterator for StackIter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(val) = self.segment.next_back() {
+                self.len -= 1;
+                return Some(val);
+            }
+            let (base, len) = unsafe { self.rest.next_segment()? };
+            self.segment = unsafe { slice::from_raw_parts(base, len) }.iter();
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<T> ExactSizeIterator for StackIter<'_, T> {}
+
+impl<T> FusedIterator for StackIter<'_, T> {}
+
+/// Iterates mutably from the top of a stack to its bottom.
+#[derive(Debug)]
+pub struct StackIterMut<'a, T> {
+    segment: slice::IterMut<'a, T>,
+    rest: Segments<T>,
+    len: usize,
+}
+
+impl<'a, T> StackIterMut<'a, T> {
+    /// # Safety
+    ///
+    /// `top` and `rest` must hold `len` values exclusively borrowed for `'a`.
+    unsafe fn new(top: (*mut T, usize), rest: Segments<T>, len: usize) -> Self {
+        Self {
+            segment: unsafe { slice::from_raw_parts_mut(top.0, top.1) }.iter_mut(),
+            rest,
+            len,
+        }
+    }
+}
+
+impl<'a, T> Iterator for StackIterMut<'a, T> {
+    type Item = &'a mut T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(val) = self.segment.next_back() {
+                self.len -= 1;
+                return Some(val);
+            }
+            let (base, len) = unsafe { self.rest.next_segment()? };
+            self.segment = unsafe { slice::from_raw_parts_mut(base, len) }.iter_mut();
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<T> ExactSizeIterator for StackIterMut<'_, T> {}
+
+impl<T> FusedIterator for StackIterMut<'_, T> {}
+
+/// A height of a stack, see `checkpoint` on [`LinkedListStack`] and [`UnboundedStack`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Mark {
+    len: usize,
+}
+
+impl Mark {
+    /// The number of values the stack held when marked.
+    pub fn height(&self) -> usize {
+        self.len
+    }
+}
+
+/// Pops the values of a stack, see `drain` and `drain_to` on each stack.
+///
+/// Values that are not consumed are dropped along with the iterator, leaving the stack
+/// empty or at the height of its mark.
+#[derive(Debug)]
+pub struct StackDrain<'a, T, S> {
+    stack: &'a mut S,
+    pop: fn(&mut S) -> T,
+    len: usize,
+}
+
+impl<T, S> Iterator for StackDrain<'_, T, S> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        (self.len > 0).then(|| {
+            self.len -= 1;
+            (self.pop)(self.stack)
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<T, S> ExactSizeIterator for StackDrain<'_, T, S> {}
+
+impl<T, S> FusedIterator for StackDrain<'_, T, S> {}
+
+impl<T, S> Drop for StackDrain<'_, T, S> {
+    fn drop(&mut self) {
+        self.for_each(drop);
+    }
+}
+
+/// Pops the values of an owned stack, see `into_iter` on each stack.
+#[derive(Debug)]
+pub struct StackIntoIter<T, S> {
+    stack: S,
+    pop: fn(&mut S) -> T,
+    len: usize,
+}
+
+impl<T, S> Iterator for StackIntoIter<T, S> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        (self.len > 0).then(|| {
+            self.len -= 1;
+            (self.pop)(&mut self.stack)
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<T, S> ExactSizeIterator for StackIntoIter<T, S> {}
+
+impl<T, S> FusedIterator for StackIntoIter<T, S> {}
+
+/// Implements `IntoIterator` for a stack, by value and by reference.
+macro_rules! impl_into_iterator {
+    ([$($params:tt)*] $stack:ty) => {
+        impl<$($params)*> IntoIterator for $stack {
+            type Item = T;
+            type IntoIter = StackIntoIter<T, Self>;
+
+            fn into_iter(self) -> Self::IntoIter {
+                StackIntoIter {
+                    len: self.len(),
+                    stack: self,
+                    pop: Self::pop,
+                }
+            }
+        }
+
+        impl<'a, $($params)*> IntoIterator for &'a $stack {
+            type Item = &'a T;
+            type IntoIter = StackIter<'a, T>;
+
+            fn into_iter(self) -> Self::IntoIter {
+                self.iter()
+            }
+        }
+
+        impl<'a, $($params)*> IntoIterator for &'a mut $stack {
+            type Item = &'a mut T;
+            type IntoIter = StackIterMut<'a, T>;
+
+            fn into_iter(self) -> Self::IntoIter {
+                self.iter_mut()
+            }
+        }
+    };
+}
+
+impl_into_iterator!([T, const N: usize] ArrayStack<T, N>);
+impl_into_iterator!([T, A: Allocator] BoundedStack<T, A>);
+impl_into_iterator!([T, P: NodePool<Node<T>>] LinkedListStack<T, P>);
+impl_into_iterator!([T, A: Allocator] UnboundedStack<T, A>);
+impl_into_iterator!([T, A: Allocator] ShadowCopyStack<T, A>);
+
+/// Implements [`Stack`] for a stack by forwarding to its inherent methods.
+macro_rules! impl_stack {
+    // Bounded stacks already give the value back
+    ([$($params:tt)*] $stack:ty, T) => {
+        impl_stack!(
+            @impl [$($params)*] $stack, T,
+            |stack: &$stack| Some(<$stack>::max_len(stack)),
+            |stack: &mut $stack, val: T| <$stack>::try_push(stack, val)
+        );
+    };
+    ([$($params:tt)*] $stack:ty, AllocError) => {
+        impl_stack!(
+            @impl [$($params)*] $stack, AllocError,
+            |_: &$stack| None,
+            |stack: &mut $stack, val: T| {
+                <$stack>::push_or_return(stack, val).map_err(|(val, _)| val)
+            }
+        );
+    };
+    (@impl [$($params:tt)*] $stack:ty, $error:ty, $max_len:expr, $try_push_or_return:expr) => {
+        impl<$($params)*> Stack<T> for $stack {
+            type Error = $error;
+
+            fn is_empty(&self) -> bool {
+                <$stack>::is_empty(self)
+            }
+
+            fn len(&self) -> usize {
+                <$stack>::len(self)
+            }
+
+            fn max_len(&self) -> Option<usize> {
+                ($max_len)(self)
+            }
+
+            fn push(&mut self, val: T) {
+                <$stack>::push(self, val)
+            }
+
+            fn try_push(&mut self, val: T) -> Result<(), Self::Error> {
+                <$stack>::try_push(self, val)
+            }
+
+            fn try_push_or_return(&mut self, val: T) -> Result<(), T> {
+                ($try_push_or_return)(self, val)
+            }
+
+            fn pop(&mut self) -> T {
+                <$stack>::pop(self)
+            }
+
+            fn peek(&self) -> &T {
+                <$stack>::peek(self)
+            }
+
+            fn peek_mut(&mut self) -> &mut T {
+                <$stack>::peek_mut(self)
+            }
         }
+    };
+}
+
+impl_stack!([T, const N: usize] ArrayStack<T, N>, T);
+impl_stack!([T, A: Allocator] BoundedStack<T, A>, T);
+impl_stack!([T, P: NodePool<Node<T>>] LinkedListStack<T, P>, AllocError);
+impl_stack!([T, A: Allocator] UnboundedStack<T, A>, AllocError);
+impl_stack!([T, A: Allocator] ShadowCopyStack<T, A>, AllocError);
+
+/// An undo log with redo, built from two stacks.
+///
+/// Recorded actions are undone from the most recent one, and undone actions can be redone
+/// until a new action is recorded, which discards them.
+#[derive(Debug)]
+pub struct UndoRedo<T, S = LinkedListStack<T>>
+where
+    S: Stack<T>,
+{
+    undo: S,
+    redo: S,
+    marker: PhantomData<T>,
+}
+
+#[cfg(feature = "alloc")]
+impl<T> Default for UndoRedo<T> {
+    fn default() -> Self {
+        Self::with_stacks(LinkedListStack::default(), LinkedListStack::default())
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T> UndoRedo<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<T, S> UndoRedo<T, S>
+where
+    S: Stack<T>,
+{
+    /// # Panics
+    ///
+    /// Panics if a stack is not empty.
+    pub fn with_stacks(undo: S, redo: S) -> Self {
+        assert!(
+            undo.is_empty() && redo.is_empty(),
+            "undo/redo built from non-empty stacks"
+        );
+        Self {
+            undo,
+            redo,
+            marker: PhantomData,
+        }
+    }
+
+    pub fn undo_len(&self) -> usize {
+        self.undo.len()
+    }
+
+    pub fn redo_len(&self) -> usize {
+        self.redo.len()
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    /// Records `action` as the most recent one, discarding every action that can be redone.
+    pub fn record(&mut self, action: T) {
+        self.undo.push(action);
+        while self.redo.try_pop().is_some() {}
+    }
+
+    pub fn try_record(&mut self, action: T) -> Result<(), S::Error> {
+        self.undo.try_push(action)?;
+        while self.redo.try_pop().is_some() {}
+        Ok(())
+    }
+
+    /// Moves the most recent action to the redo stack, returning it so that it can be
+    /// reverted.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the redo stack can't hold the action, which is then kept on the undo stack.
+    pub fn undo(&mut self) -> Option<&T> {
+        self.try_undo()
+            .unwrap_or_else(|_| panic!("overflow: no room to undo an action"))
+    }
+
+    /// Same as [`undo`](Self::undo), but if the redo stack can't hold the action, it is kept
+    /// on the undo stack and returned as the error.
+    pub fn try_undo(&mut self) -> Result<Option<&T>, &T> {
+        let Some(action) = self.undo.try_pop() else {
+            return Ok(None);
+        };
+        match self.redo.try_push_or_return(action) {
+            Ok(()) => Ok(Some(self.redo.peek())),
+            Err(action) => {
+                Self::put_back(&mut self.undo, action);
+                Err(self.undo.peek())
+            }
+        }
+    }
+
+    /// Moves the last undone action back to the undo stack, returning it so that it can be
+    /// applied again.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the undo stack can't hold the action, which is then kept on the redo stack.
+    pub fn redo(&mut self) -> Option<&T> {
+        self.try_redo()
+            .unwrap_or_else(|_| panic!("overflow: no room to redo an action"))
+    }
+
+    /// Same as [`redo`](Self::redo), but if the undo stack can't hold the action, it is kept
+    /// on the redo stack and returned as the error.
+    pub fn try_redo(&mut self) -> Result<Option<&T>, &T> {
+        let Some(action) = self.redo.try_pop() else {
+            return Ok(None);
+        };
+        match self.undo.try_push_or_return(action) {
+            Ok(()) => Ok(Some(self.undo.peek())),
+            Err(action) => {
+                Self::put_back(&mut self.redo, action);
+                Err(self.redo.peek())
+            }
+        }
+    }
+
+    /// Pushes `action` back onto the stack it was just popped from.
+    fn put_back(stack: &mut S, action: T) {
+        // Only an allocating stack could refuse it, pushing then handles the failure
+        if let Err(action) = stack.try_push_or_return(action) {
+            stack.push(action);
+        }
+    }
+
+    /// The most recent action, the next one to be undone.
+    pub fn last(&self) -> Option<&T> {
+        self.undo.try_peek()
+    }
+
+    /// Discards every action, undone or not.
+    pub fn clear(&mut self) {
+        while self.undo.try_pop().is_some() {}
+        while self.redo.try_pop().is_some() {}
+    }
+}
+
+/// An associative operation with an identity, folded over the values of an
+/// [`AggregateStack`] or an [`AggregateQueue`](crate::queues::AggregateQueue).
+///
+/// Only associativity is required, `combine` is always given its operands in the order
+/// of the values they come from.
+pub trait Monoid<T> {
+    type Output: Clone;
+
+    /// The aggregate of no values.
+    fn empty(&self) -> Self::Output;
+
+    /// The aggregate of `val` alone.
+    fn lift(&self, val: &T) -> Self::Output;
+
+    fn combine(&self, lhs: &Self::Output, rhs: &Self::Output) -> Self::Output;
+}
+
+/// The minimum of the values, `None` if there are none.
+///
+/// The aggregate stored along each value is a clone of the minimum so far, hence the
+/// `T: Clone` bound. Values that are costly to clone can be held behind an `Rc`, or
+/// aggregated as indices into a collection held elsewhere.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Min;
+
+impl<T> Monoid<T> for Min
+where
+    T: Ord + Clone,
+{
+    type Output = Option<T>;
+
+    fn empty(&self) -> Option<T> {
+        None
+    }
+
+    fn lift(&self, val: &T) -> Option<T> {
+        Some(val.clone())
+    }
+
+    fn combine(&self, lhs: &Option<T>, rhs: &Option<T>) -> Option<T> {
+        match (lhs, rhs) {
+            (Some(lhs), Some(rhs)) => Some(lhs.min(rhs).clone()),
+            (val, None) | (None, val) => val.clone(),
+        }
+    }
+}
+
+/// The maximum of the values, `None` if there are none.
+///
+/// Stores clones of the maximum so far, see [`Min`].
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Max;
+
+impl<T> Monoid<T> for Max
+where
+    T: Ord + Clone,
+{
+    type Output = Option<T>;
+
+    fn empty(&self) -> Option<T> {
+        None
+    }
+
+    fn lift(&self, val: &T) -> Option<T> {
+        Some(val.clone())
+    }
+
+    fn combine(&self, lhs: &Option<T>, rhs: &Option<T>) -> Option<T> {
+        match (lhs, rhs) {
+            (Some(lhs), Some(rhs)) => Some(lhs.max(rhs).clone()),
+            (val, None) | (None, val) => val.clone(),
+        }
+    }
+}
+
+/// A stack that keeps the aggregate of its values, from bottom to top, in O(1).
+///
+/// Each value is stored on the underlying stack along with the aggregate of the values up
+/// to it, so values cannot be mutated in place.
+#[derive(Debug)]
+pub struct AggregateStack<T, M, S = LinkedListStack<(T, <M as Monoid<T>>::Output)>>
+where
+    M: Monoid<T>,
+    S: Stack<(T, M::Output)>,
+{
+    stack: S,
+    monoid: M,
+    marker: PhantomData<T>,
+}
+
+/// The error of a [`Stack`] of `(T, aggregate)` pairs, stripped of the aggregate, so that
+/// an [`AggregateStack`] fails like the stack it is built on.
+pub trait AggregateError<T> {
+    /// The value itself for bounded stacks, an [`AllocError`] for the others.
+    type Error;
+
+    fn without_aggregate(self) -> Self::Error;
+}
+
+impl<T, O> AggregateError<T> for (T, O) {
+    type Error = T;
+
+    fn without_aggregate(self) -> T {
+        self.0
+    }
+}
+
+impl<T> AggregateError<T> for AllocError {
+    type Error = AllocError;
+
+    fn without_aggregate(self) -> AllocError {
+        self
+    }
+}
+
+/// A stack that keeps track of its minimum.
+pub type MinStack<T, S = LinkedListStack<(T, Option<T>)>> = AggregateStack<T, Min, S>;
+
+/// A stack that keeps track of its maximum.
+pub type MaxStack<T, S = LinkedListStack<(T, Option<T>)>> = AggregateStack<T, Max, S>;
+
+#[cfg(feature = "alloc")]
+impl<T, M> Default for AggregateStack<T, M>
+where
+    M: Monoid<T> + Default,
+{
+    fn default() -> Self {
+        Self::with_monoid(M::default())
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T, M> AggregateStack<T, M>
+where
+    M: Monoid<T>,
+{
+    pub fn new() -> Self
+    where
+        M: Default,
+    {
+        Self::default()
+    }
+
+    pub fn with_monoid(monoid: M) -> Self {
+        Self::with_stack(monoid, LinkedListStack::default())
+    }
+}
+
+impl<T, M, S> AggregateStack<T, M, S>
+where
+    M: Monoid<T>,
+    S: Stack<(T, M::Output)>,
+{
+    /// # Panics
+    ///
+    /// Panics if `stack` is not empty.
+    pub fn with_stack(monoid: M, stack: S) -> Self {
+        assert!(stack.is_empty(), "aggregate built from a non-empty stack");
+        Self {
+            stack,
+            monoid,
+            marker: PhantomData,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stack.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.stack.len()
+    }
+
+    pub fn monoid(&self) -> &M {
+        &self.monoid
+    }
+
+    pub fn push(&mut self, val: T) {
+        let aggregate = self.aggregate_with(&val);
+        self.stack.push((val, aggregate));
+    }
+
+    pub fn try_push(&mut self, val: T) -> Result<(), <S::Error as AggregateError<T>>::Error>
+    where
+        S::Error: AggregateError<T>,
+    {
+        let aggregate = self.aggregate_with(&val);
+        self.stack
+            .try_push((val, aggregate))
+            .map_err(AggregateError::without_aggregate)
+    }
+
+    pub fn pop(&mut self) -> T {
+        self.stack.pop().0
+    }
+
+    pub fn peek(&self) -> &T {
+        &self.stack.peek().0
+    }
+
+    pub fn try_pop(&mut self) -> Option<T> {
+        self.stack.try_pop().map(|(val, _)| val)
+    }
+
+    pub fn try_peek(&self) -> Option<&T> {
+        self.stack.try_peek().map(|(val, _)| val)
+    }
+
+    /// The aggregate of every value, from the bottom of the stack to its top.
+    pub fn aggregate(&self) -> M::Output {
+        self.top_aggregate()
+            .cloned()
+            .unwrap_or_else(|| self.monoid.empty())
+    }
+
+    pub(crate) fn top_aggregate(&self) -> Option<&M::Output> {
+        self.stack.try_peek().map(|(_, aggregate)| aggregate)
+    }
+
+    fn aggregate_with(&self, val: &T) -> M::Output {
+        let val = self.monoid.lift(val);
+        match self.top_aggregate() {
+            Some(below) => self.monoid.combine(below, &val),
+            None => val,
+        }
+    }
+}
+
+impl<T, S> AggregateStack<T, Min, S>
+where
+    T: Ord + Clone,
+    S: Stack<(T, Option<T>)>,
+{
+    pub fn min(&self) -> Option<&T> {
+        self.top_aggregate().and_then(Option::as_ref)
+    }
+}
+
+impl<T, S> AggregateStack<T, Max, S>
+where
+    T: Ord + Clone,
+    S: Stack<(T, Option<T>)>,
+{
+    pub fn max(&self) -> Option<&T> {
+        self.top_aggregate().and_then(Option::as_ref)
     }
 }
 
@@ -481,6 +1838,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "alloc")]
     fn bounded_stack_ok() {
         let mut stack = BoundedStack::new(10);
         stack.push(3);
@@ -506,6 +1864,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "alloc")]
     #[should_panic(expected = "underflow: popping from an empty stack")]
     fn bounded_stack_underflow() {
         let mut stack = BoundedStack::new(1);
@@ -516,6 +1875,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "alloc")]
     #[should_panic(expected = "overflow: pushing to a full stack")]
     fn bounded_stack_overflow() {
         let mut stack = BoundedStack::new(1);
@@ -524,6 +1884,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "alloc")]
     fn linked_list_stack_ok() {
         let mut stack = LinkedListStack::new(2, 1);
         stack.push(3);
@@ -549,6 +1910,110 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "alloc")]
+    fn linked_list_stack_try_push() {
+        let mut stack = LinkedListStack::new(2, 1);
+        assert_eq!(Ok(()), stack.try_reserve(5));
+        for i in 0..5 {
+            assert_eq!(Ok(()), stack.try_push(i));
+        }
+        assert_eq!(5, stack.len());
+        assert_eq!(4, stack.pop());
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn linked_list_stack_allocator_stats() {
+        let mut stack = LinkedListStack::new(4, 1);
+        for i in 0..6 {
+            stack.push(i);
+        }
+        stack.pop();
+        let stats = stack.allocator_stats();
+        assert_eq!(2, stats.blocks);
+        assert_eq!(5, stats.live_nodes);
+        assert_eq!(1, stats.free_nodes);
+        assert_eq!(6, stats.peak_live_nodes);
+    }
+
+    #[test]
+    fn intrusive_stack_ok() {
+        let mut nodes: [Node<usize>; 4] = core::array::from_fn(|i| Node {
+            next: ptr::null_mut(),
+            val: MaybeUninit::new(i),
+        });
+        let mut stack = IntrusiveStack::new();
+        for node in &mut nodes {
+            stack.push(node);
+        }
+        assert_eq!(4, stack.len());
+        assert_eq!(3, unsafe { stack.peek().val.assume_init() });
+        for i in (0..4).rev() {
+            let node = stack.pop();
+            assert!(node.next.is_null());
+            assert_eq!(i, unsafe { node.val.assume_init() });
+        }
+        assert!(stack.is_empty());
+    }
+
+    #[test]
+    fn stacks_try_new_in_region() {
+        use crate::allocator::Region;
+        static mut REGION: [MaybeUninit<u8>; 64] = [MaybeUninit::uninit(); 64];
+        let region = Region::new(unsafe { &mut *ptr::addr_of_mut!(REGION) });
+        assert!(BoundedStack::<u64, _>::try_new_in(16, &region).is_err());
+        assert!(UnboundedStack::<u64, _>::try_new_in(16, &region).is_err());
+        assert!(ShadowCopyStack::<u64, _>::try_new_in(16, &region).is_err());
+        assert_eq!(
+            Some(AllocError::CapacityOverflow),
+            UnboundedStack::<u64, _>::try_new_in(usize::MAX, &region).err()
+        );
+
+        let mut stack = ShadowCopyStack::try_new_in(4, &region).unwrap();
+        stack.push(1);
+        assert_eq!(1, stack.pop());
+    }
+
+    #[test]
+    fn unbounded_stack_region() {
+        use crate::allocator::Region;
+        static mut REGION: [MaybeUninit<u8>; 512] = [MaybeUninit::uninit(); 512];
+        let region = Region::new(unsafe { &mut *ptr::addr_of_mut!(REGION) });
+        let mut stack = UnboundedStack::new_in(4, &region);
+        let mut pushed = 0u64;
+        while stack.try_push(pushed).is_ok() {
+            pushed += 1;
+        }
+        assert!(pushed > 4);
+        assert!(stack.iter().copied().eq((0..pushed).rev()));
+        for i in (0..pushed).rev() {
+            assert_eq!(i, stack.pop());
+        }
+        assert!(stack.is_empty());
+    }
+
+    #[test]
+    fn linked_list_stack_region() {
+        static mut REGION: [MaybeUninit<u8>; 256] = [MaybeUninit::uninit(); 256];
+        let region = unsafe { &mut *ptr::addr_of_mut!(REGION) };
+        let mut stack = LinkedListStack::with_allocator(BlockAllocator::from_region(region, 4, 2));
+        let mut pushed = 0;
+        while stack.try_push(pushed).is_ok() {
+            pushed += 1;
+        }
+        // The region has room left, but the blocks array does not grow
+        assert_eq!(8, pushed);
+        assert_eq!(Err(pushed), Stack::try_push_or_return(&mut stack, pushed));
+        assert_eq!(pushed, stack.len());
+        for i in (0..pushed).rev() {
+            assert_eq!(i, stack.pop());
+        }
+        stack.push(42);
+        assert_eq!(&42, stack.peek());
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
     #[should_panic(expected = "underflow: popping from an empty stack")]
     fn linked_list_stack_underflow() {
         let mut stack = LinkedListStack::new(4, 2);
@@ -559,6 +2024,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "alloc")]
     fn unbounded_stack_ok() {
         let mut stack = UnboundedStack::new(2);
         stack.push(3);
@@ -584,6 +2050,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "alloc")]
     #[should_panic(expected = "underflow: popping from an empty stack")]
     fn unbounded_stack_underflow() {
         let mut stack = UnboundedStack::new(4);
@@ -594,6 +2061,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "alloc")]
     fn shadow_copy_stack_ok() {
         let mut stack = ShadowCopyStack::new(2);
         stack.push(3);
@@ -616,9 +2084,423 @@ mod tests {
             assert_eq!(i, stack.pop());
         }
         assert!(stack.is_empty());
+
+        // Whatever the capacity, the copy completes before the array gets full
+        for max_size in 1..100 {
+            let mut stack = ShadowCopyStack::new(max_size);
+            for i in 0..4 * max_size {
+                stack.push(i);
+                assert!(stack.len() < stack.max_size);
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn unbounded_stack_spare_chunk() {
+        let mut stack = UnboundedStack::new(2);
+        for i in 0..3 {
+            stack.push(i);
+        }
+        let chunk = stack.base;
+        // Going back and forth across the chunk boundary reuses the same chunk
+        for _ in 0..3 {
+            stack.pop();
+            stack.pop();
+            assert!(!stack.spare.is_null());
+            stack.push(1);
+            stack.push(2);
+            assert!(stack.spare.is_null());
+            assert_eq!(chunk, stack.base);
+        }
+        stack.pop();
+        stack.pop();
+        stack.shrink_to_fit();
+        assert!(stack.spare.is_null());
+        assert!(stack.drain().eq([0]));
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn shadow_copy_stack_shrink() {
+        let mut stack = ShadowCopyStack::new(4);
+        for i in 0..64 {
+            stack.push(i);
+        }
+        let max_size = stack.max_size;
+        for i in (8..64).rev() {
+            assert_eq!(i, stack.pop());
+        }
+        assert!(stack.max_size < max_size);
+        assert!(stack.iter().copied().eq((0..8).rev()));
+        while !stack.is_empty() {
+            stack.pop();
+        }
+        stack.push(0);
+        // Never smaller than the initial capacity
+        assert_eq!(4, stack.max_size);
+        assert!(stack.copy.is_null());
+
+        // Push and pop around the halving threshold without copying back and forth
+        for i in 1..3 {
+            stack.push(i);
+        }
+        for _ in 0..8 {
+            stack.pop();
+            stack.push(0);
+            assert!(stack.copy.is_null());
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn stacks_checked_ops() {
+        let mut array: ArrayStack<usize, 1> = ArrayStack::new();
+        assert_eq!(None, array.try_pop());
+        assert_eq!(None, array.try_peek());
+        assert_eq!(Ok(()), array.try_push(1));
+        assert_eq!(Err(2), array.try_push(2));
+        *array.peek_mut() += 1;
+        assert_eq!(Some(2), array.try_pop());
+
+        let mut bounded = BoundedStack::new(1);
+        assert_eq!(Ok(()), bounded.try_push(1));
+        assert_eq!(Err(2), bounded.try_push(2));
+        *bounded.try_peek_mut().unwrap() = 3;
+        assert_eq!(Some(&3), bounded.try_peek());
+        assert_eq!(Some(3), bounded.try_pop());
+        assert_eq!(None, bounded.try_peek_mut());
+
+        let mut linked = LinkedListStack::new(2, 1);
+        let mut unbounded = UnboundedStack::new(2);
+        let mut shadow = ShadowCopyStack::new(2);
+        for i in 0..3 {
+            linked.push(i);
+            unbounded.push(i);
+            shadow.push(i);
+        }
+        *linked.peek_mut() = 10;
+        *unbounded.peek_mut() = 10;
+        *shadow.peek_mut() = 10;
+        for expected in [10, 1, 0] {
+            assert_eq!(Some(&mut expected.clone()), linked.try_peek_mut());
+            assert_eq!(Some(&expected), unbounded.try_peek());
+            assert_eq!(Some(expected), linked.try_pop());
+            assert_eq!(Some(expected), unbounded.try_pop());
+            assert_eq!(Some(expected), shadow.try_pop());
+        }
+        assert_eq!(None, linked.try_pop());
+        assert_eq!(None, unbounded.try_peek_mut());
+        assert_eq!(None, shadow.try_pop());
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn stacks_checkpoint() {
+        let mut linked = LinkedListStack::new(2, 1);
+        let mut unbounded = UnboundedStack::new(2);
+        linked.push(0);
+        unbounded.push(0);
+        let (linked_mark, unbounded_mark) = (linked.checkpoint(), unbounded.checkpoint());
+        assert_eq!(1, linked_mark.height());
+        for i in 1..6 {
+            linked.push(i);
+            unbounded.push(i);
+        }
+        assert!(linked.drain_to(linked_mark).eq((1..6).rev()));
+        assert_eq!(1, linked.len());
+
+        // Values left in the iterator are dropped, and chunks freed along the way
+        let mut drain = unbounded.drain_to(unbounded_mark);
+        assert_eq!(Some(5), drain.next());
+        drop(drain);
+        assert_eq!(&0, unbounded.peek());
+        unbounded.push(1);
+        let mark = unbounded.checkpoint();
+        unbounded.rollback_to(mark);
+        assert_eq!(2, unbounded.len());
+        unbounded.rollback_to(unbounded_mark);
+        assert!(unbounded.iter().eq([&0]));
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    #[should_panic(expected = "rollback: the stack is below the mark")]
+    fn stacks_stale_mark() {
+        let mut stack = LinkedListStack::new(2, 1);
+        stack.push(0);
+        let mark = stack.checkpoint();
+        stack.pop();
+        stack.rollback_to(mark);
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn undo_redo() {
+        let mut log = UndoRedo::new();
+        assert_eq!(None, log.undo());
+        log.record(1);
+        log.record(2);
+        log.record(3);
+        assert_eq!(Some(&3), log.undo());
+        assert_eq!(Some(&2), log.undo());
+        assert_eq!((1, 2), (log.undo_len(), log.redo_len()));
+        assert_eq!(Some(&2), log.redo());
+        assert_eq!(Some(&2), log.last());
+
+        // Recording discards what can be redone
+        log.record(4);
+        assert!(!log.can_redo());
+        assert_eq!(None, log.redo());
+        assert_eq!(Some(&4), log.undo());
+        assert_eq!(Some(&2), log.undo());
+        assert_eq!(Some(&1), log.undo());
+        assert!(!log.can_undo());
+
+        let mut bounded = UndoRedo::with_stacks(BoundedStack::new(1), BoundedStack::new(1));
+        assert_eq!(Ok(()), bounded.try_record(1));
+        assert_eq!(Err(2), bounded.try_record(2));
+        bounded.clear();
+        assert_eq!((0, 0), (bounded.undo_len(), bounded.redo_len()));
+
+        // Actions that can't be moved stay where they were
+        let mut uneven = UndoRedo::with_stacks(BoundedStack::new(4), BoundedStack::new(1));
+        for action in 1..=3 {
+            uneven.record(action);
+        }
+        assert_eq!(Ok(Some(&3)), uneven.try_undo());
+        assert_eq!(Err(&2), uneven.try_undo());
+        assert_eq!((2, 1), (uneven.undo_len(), uneven.redo_len()));
+        assert_eq!(Ok(Some(&3)), uneven.try_redo());
+        assert_eq!(Some(&3), uneven.last());
+        assert_eq!(3, uneven.undo_len());
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    #[should_panic(expected = "overflow: no room to undo an action")]
+    fn undo_redo_overflow() {
+        let mut log = UndoRedo::with_stacks(BoundedStack::new(2), BoundedStack::new(1));
+        log.record(1);
+        log.record(2);
+        log.undo();
+        log.undo();
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn min_stack() {
+        let mut stack = MinStack::new();
+        assert_eq!(None, stack.min());
+        for val in [3, 1, 2, 1] {
+            stack.push(val);
+        }
+        assert_eq!(Some(&1), stack.min());
+        stack.pop();
+        stack.pop();
+        assert_eq!(Some(&1), stack.min());
+        assert_eq!(1, stack.pop());
+        assert_eq!(Some(&3), stack.min());
+
+        let mut stack = MaxStack::with_stack(Max, BoundedStack::new(2));
+        assert_eq!(Ok(()), stack.try_push(1));
+        assert_eq!(Ok(()), stack.try_push(4));
+        assert_eq!(Err(2), stack.try_push(2));
+        assert_eq!(Some(&4), stack.max());
+        assert_eq!(Some(4), stack.aggregate());
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn aggregate_stack_gcd() {
+        #[derive(Default)]
+        struct Gcd;
+
+        impl Monoid<u64> for Gcd {
+            type Output = u64;
+
+            fn empty(&self) -> u64 {
+                0
+            }
+
+            fn lift(&self, val: &u64) -> u64 {
+                *val
+            }
+
+            fn combine(&self, lhs: &u64, rhs: &u64) -> u64 {
+                let (mut a, mut b) = (*lhs, *rhs);
+                while b != 0 {
+                    (a, b) = (b, a % b);
+                }
+                a
+            }
+        }
+
+        let mut stack = AggregateStack::<u64, Gcd>::new();
+        assert_eq!(0, stack.aggregate());
+        for (val, gcd) in [(12, 12), (18, 6), (8, 2)] {
+            stack.push(val);
+            assert_eq!(gcd, stack.aggregate());
+        }
+        stack.pop();
+        assert_eq!(6, stack.aggregate());
+        assert_eq!(&18, stack.peek());
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn stack_trait() {
+        fn reverse<S>(stack: &mut S, vals: &[usize]) -> usize
+        where
+            S: Stack<usize>,
+        {
+            for &val in vals {
+                if stack.try_push(val).is_err() {
+                    break;
+                }
+            }
+            *stack.peek_mut() += 100;
+            let mut reversed = 0;
+            while let Some(val) = stack.try_pop() {
+                reversed = reversed * 1000 + val;
+            }
+            reversed
+        }
+
+        let vals = [1, 2, 3];
+        assert_eq!(103_002_001, reverse(&mut ArrayStack::<_, 4>::new(), &vals));
+        assert_eq!(102_001, reverse(&mut BoundedStack::new(2), &vals));
+        assert_eq!(103_002_001, reverse(&mut LinkedListStack::new(2, 1), &vals));
+        assert_eq!(103_002_001, reverse(&mut UnboundedStack::new(2), &vals));
+        assert_eq!(103_002_001, reverse(&mut ShadowCopyStack::new(2), &vals));
+    }
+
+    #[test]
+    fn array_stack_iter() {
+        let mut stack: ArrayStack<usize, 8> = ArrayStack::new();
+        for i in 0..6 {
+            stack.push(i);
+        }
+        assert!(stack.iter().eq([5, 4, 3, 2, 1, 0].iter()));
+        for val in &mut stack {
+            *val *= 10;
+        }
+        let mut drain = stack.drain();
+        assert_eq!(6, drain.len());
+        assert_eq!(Some(50), drain.next());
+        drop(drain);
+        assert!(stack.is_empty());
+
+        stack.push(1);
+        stack.push(2);
+        assert!(stack.into_iter().eq([2, 1]));
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn bounded_stack_iter() {
+        let mut stack = BoundedStack::new(4);
+        for i in 0..4 {
+            stack.push(i);
+        }
+        stack.iter_mut().for_each(|val| *val += 1);
+        assert!((&stack).into_iter().eq([4, 3, 2, 1].iter()));
+        assert!(stack.drain().eq([4, 3, 2, 1]));
+        assert!(stack.is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn linked_list_stack_iter() {
+        let mut stack = LinkedListStack::new(4, 1);
+        assert_eq!(None, stack.iter().next());
+        for i in 0..6 {
+            stack.push(i);
+        }
+        stack.iter_mut().for_each(|val| *val *= 2);
+        assert!(stack.iter().eq([10, 8, 6, 4, 2, 0].iter()));
+        assert!(stack.drain().take(2).eq([10, 8]));
+        assert!(stack.is_empty());
+
+        stack.push(1);
+        stack.push(2);
+        assert!(stack.into_iter().eq([2, 1]));
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn unbounded_stack_iter() {
+        let mut stack = UnboundedStack::new(2);
+        for i in 0..5 {
+            stack.push(i);
+        }
+        // The current chunk is empty after popping its only value
+        stack.pop();
+        assert_eq!(4, stack.iter().len());
+        assert!(stack.iter().eq([3, 2, 1, 0].iter()));
+        stack.iter_mut().for_each(|val| *val += 1);
+        assert!(stack.drain().eq([4, 3, 2, 1]));
+        assert!(stack.is_empty());
+
+        for i in 0..5 {
+            stack.push(i);
+        }
+        assert!(stack.into_iter().eq((0..5).rev()));
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn unbounded_stack_zero_sized() {
+        let mut stack = UnboundedStack::new(4);
+        for _ in 0..10 {
+            stack.push(());
+        }
+        assert_eq!(10, stack.iter().count());
+        assert_eq!(10, stack.iter_mut().len());
+        assert_eq!((), stack.pop());
+        assert_eq!(9, stack.drain().count());
+        assert!(stack.is_empty());
+        assert_eq!(None, stack.try_pop());
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn shadow_copy_stack_iter() {
+        use std::boxed::Box;
+
+        let mut stack = ShadowCopyStack::new(16);
+        for i in 0..12 {
+            stack.push(Box::new(i));
+        }
+        // Copy in progress, values already copied are read from the copy
+        assert!(!stack.copy.is_null() && stack.copy_size > 0);
+        // Replacing boxes frees the old ones, which must not be freed again
+        for val in stack.iter_mut() {
+            let doubled = Box::new(**val * 2);
+            drop(mem::replace(val, doubled));
+        }
+        assert!(stack
+            .iter()
+            .map(|val| **val)
+            .eq((0..12).rev().map(|i| i * 2)));
+
+        for i in 12..16 {
+            stack.push(Box::new(i * 2));
+        }
+        assert!(stack.copy.is_null());
+        assert!(stack
+            .drain()
+            .take(8)
+            .map(|val| *val)
+            .eq((8..16).rev().map(|i| i * 2)));
+        for i in 0..4 {
+            stack.push(Box::new(i));
+        }
+        assert!(stack.into_iter().map(|val| *val).eq([3, 2, 1, 0]));
     }
 
     #[test]
+    #[cfg(feature = "alloc")]
     #[should_panic(expected = "underflow: popping from an empty stack")]
     fn shadow_copy_stack_underflow() {
         let mut stack = ShadowCopyStack::new(4);
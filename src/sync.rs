@@ -0,0 +1,27 @@
+//! Atomics, swapped for [loom](https://docs.rs/loom)'s ones when built with `--cfg loom`.
+
+#[cfg(not(loom))]
+pub(crate) use core::sync::atomic;
+#[cfg(loom)]
+pub(crate) use loom::sync::atomic;
+
+#[cfg(loom)]
+pub(crate) use loom::cell;
+
+#[cfg(not(loom))]
+pub(crate) mod cell {
+    /// `core`'s `UnsafeCell` behind the closure-based API of loom's one, so that loom can
+    /// check accesses to it.
+    #[derive(Debug)]
+    pub(crate) struct UnsafeCell<T>(core::cell::UnsafeCell<T>);
+
+    impl<T> UnsafeCell<T> {
+        pub(crate) const fn new(val: T) -> Self {
+            Self(core::cell::UnsafeCell::new(val))
+        }
+
+        pub(crate) fn with_mut<R>(&self, f: impl FnOnce(*mut T) -> R) -> R {
+            f(self.0.get())
+        }
+    }
+}
@@ -1,20 +1,22 @@
-use alloc::boxed::Box;
-use core::borrow::Borrow;
-use core::iter::FusedIterator;
-use core::mem::{self, ManuallyDrop, MaybeUninit};
-use core::ops::Range;
+use core::mem::{self, MaybeUninit};
 use core::ptr;
 
-use crate::allocator::{BlockAllocator, Nodable};
+use super::search_tree::{try_box, unbox};
+use crate::allocator::{Allocator, AllocatorStats, BlockAllocator, Global, Nodable};
 use crate::stacks::LinkedListStack;
 
 #[derive(Debug)]
-pub struct HeightBalancedTree<K, V> {
-    allocator: BlockAllocator<TreeNode<K, V>>,
+pub struct HeightBalancedTree<K, V, A = Global>
+where
+    A: Allocator,
+{
+    allocator: BlockAllocator<TreeNode<K, V>, A>,
+    alloc: A,
     root: *mut TreeNode<K, V>,
     length: usize,
 }
 
+#[cfg(feature = "alloc")]
 impl<K, V> Default for HeightBalancedTree<K, V>
 where
     K: Ord + Clone,
@@ -27,15 +29,28 @@ where
     }
 }
 
+#[cfg(feature = "alloc")]
 impl<K, V> HeightBalancedTree<K, V>
 where
     K: Ord + Clone,
 {
     pub fn new(block_size: usize, blocks_cap: usize) -> Self {
-        let mut allocator = BlockAllocator::new(block_size, blocks_cap);
+        Self::new_in(block_size, blocks_cap, Global)
+    }
+}
+
+impl<K, V, A> HeightBalancedTree<K, V, A>
+where
+    K: Ord + Clone,
+    A: Allocator + Clone,
+{
+    /// Creates a tree allocating its node blocks and values with `alloc`.
+    pub fn new_in(block_size: usize, blocks_cap: usize, alloc: A) -> Self {
+        let mut allocator = BlockAllocator::new_in(block_size, blocks_cap, alloc.clone());
         let root = allocator.get_node();
         Self {
             allocator,
+            alloc,
             root,
             length: 0,
         }
@@ -49,17 +64,30 @@ where
         self.length
     }
 
+    /// Releases the node blocks left entirely unused by removals.
+    pub fn shrink_to_fit(&mut self) {
+        self.allocator.shrink_to_fit();
+    }
+
+    pub fn allocator_stats(&self) -> AllocatorStats {
+        self.allocator.stats()
+    }
+
     pub fn insert(&mut self, key: K, value: V) -> Option<V> {
         self.length += 1;
         unsafe {
             if (*self.root).is_empty() {
-                (*self.root).left = TreePtr::Val(Box::into_raw(Box::new(value)));
+                (*self.root).left = TreePtr::Val(self.boxed(value));
                 (*self.root).key = MaybeUninit::new(key);
                 (*self.root).height = 0;
                 return None;
             }
 
-            let mut nodes = LinkedListStack::default();
+            let mut nodes = LinkedListStack::new_in(
+                BlockAllocator::<TreeNode<K, V>, A>::DEFAULT_BLOCK_SIZE,
+                BlockAllocator::<TreeNode<K, V>, A>::DEFAULT_BLOCK_CAP,
+                self.alloc.clone(),
+            );
             let mut tmp_node = self.root;
             while !(*tmp_node).right.is_null() {
                 nodes.push(tmp_node);
@@ -71,9 +99,9 @@ where
             }
 
             if &key == (*tmp_node).key.assume_init_ref() {
-                let mut val_ptr = Box::into_raw(Box::new(value));
+                let mut val_ptr = self.boxed(value);
                 mem::swap(&mut val_ptr, (*tmp_node).left.as_val_mut());
-                return Some(*Box::from_raw(val_ptr));
+                return Some(unbox(&self.alloc, val_ptr));
             }
 
             // TODO: impl that
@@ -83,7 +111,7 @@ where
                 (*old_leaf).key = MaybeUninit::new((*tmp_node).key.assume_init_read());
 
                 let new_leaf = self.allocator.get_node();
-                (*new_leaf).left = TreePtr::Val(Box::into_raw(Box::new(value)));
+                (*new_leaf).left = TreePtr::Val(self.boxed(value));
                 (*new_leaf).key = MaybeUninit::new(key.clone());
 
                 (*tmp_node).left = TreePtr::Node(old_leaf);
@@ -95,7 +123,7 @@ where
                 (*old_leaf).key = MaybeUninit::new((*tmp_node).key.assume_init_read().clone());
 
                 let new_leaf = self.allocator.get_node();
-                (*new_leaf).left = TreePtr::Val(Box::into_raw(Box::new(value)));
+                (*new_leaf).left = TreePtr::Val(self.boxed(value));
                 (*new_leaf).key = MaybeUninit::new(key);
 
                 (*tmp_node).left = TreePtr::Node(new_leaf);
@@ -104,6 +132,10 @@ where
             None
         }
     }
+
+    fn boxed(&self, value: V) -> *mut V {
+        try_box(&self.alloc, value).unwrap_or_else(|err| err.handle())
+    }
 }
 
 ////////////////////////////////////////////////////////////////////////////////////////
@@ -237,3 +269,21 @@ impl<K, V> TreeNode<K, V> {
 }
 
 ////////////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::allocator::Region;
+
+    #[test]
+    fn height_balanced_tree_region() {
+        // Room for the traversal stack as well, whose blocks have the default size
+        static mut REGION: [MaybeUninit<u8>; 65536] = [MaybeUninit::uninit(); 65536];
+        let region = Region::new(unsafe { &mut *ptr::addr_of_mut!(REGION) });
+        let mut tree = HeightBalancedTree::new_in(8, 2, &region);
+        assert_eq!(None, tree.insert(1, 10));
+        assert_eq!(None, tree.insert(2, 20));
+        assert_eq!(Some(20), tree.insert(2, 21));
+        assert_eq!(None, tree.insert(0, 0));
+    }
+}
@@ -1,20 +1,28 @@
-use alloc::boxed::Box;
+use core::alloc::Layout;
 use core::borrow::Borrow;
 use core::iter::FusedIterator;
 use core::mem::{self, ManuallyDrop, MaybeUninit};
 use core::ops::Range;
-use core::ptr;
+use core::ptr::{self, NonNull};
 
-use crate::allocator::{BlockAllocator, Nodable};
-use crate::stacks::{BoundedStack, LinkedListStack};
+use crate::allocator::{
+    AllocError, Allocator, AllocatorStats, BlockAllocator, Global, Nodable, Node, NodePool,
+};
+use crate::stacks::{ArrayStack, LinkedListStack};
 
 #[derive(Debug)]
-pub struct SearchTree<K, V> {
-    allocator: BlockAllocator<TreeNode<K, V>>,
+pub struct SearchTree<K, V, A = Global, P = BlockAllocator<TreeNode<K, V>, A>>
+where
+    A: Allocator,
+    P: NodePool<TreeNode<K, V>>,
+{
+    allocator: P,
+    alloc: A,
     root: *mut TreeNode<K, V>,
     length: usize,
 }
 
+#[cfg(feature = "alloc")]
 impl<K, V> Default for SearchTree<K, V>
 where
     K: Ord + Clone,
@@ -27,15 +35,41 @@ where
     }
 }
 
+#[cfg(feature = "alloc")]
 impl<K, V> SearchTree<K, V>
 where
     K: Ord + Clone,
 {
     pub fn new(block_size: usize, blocks_cap: usize) -> Self {
-        let mut allocator = BlockAllocator::new(block_size, blocks_cap);
+        Self::new_in(block_size, blocks_cap, Global)
+    }
+
+    /// Top-down contruction of an optimal [`SearchTree`]().
+    ///
+    /// # Panics
+    ///
+    /// Panics if `iter` is not sorted (by `K`) or if it contains duplicates.
+    pub fn from_sorted<I>(iter: I) -> Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+        I::IntoIter: ExactSizeIterator,
+    {
+        Self::from_sorted_in(iter, Global)
+    }
+}
+
+impl<K, V, A, P> SearchTree<K, V, A, P>
+where
+    K: Ord + Clone,
+    A: Allocator,
+    P: NodePool<TreeNode<K, V>>,
+{
+    /// Creates a tree drawing its nodes from `allocator` and allocating values with `alloc`.
+    pub fn with_allocator(mut allocator: P, alloc: A) -> Self {
         let root = allocator.get_node();
         Self {
             allocator,
+            alloc,
             root,
             length: 0,
         }
@@ -73,12 +107,17 @@ where
     }
 
     pub fn insert(&mut self, key: K, value: V) -> Option<V> {
-        self.length += 1;
+        self.try_insert(key, value)
+            .unwrap_or_else(|err| err.handle())
+    }
+
+    pub fn try_insert(&mut self, key: K, value: V) -> Result<Option<V>, AllocError> {
         unsafe {
             if (*self.root).is_empty() {
-                (*self.root).left = TreePtr::Val(Box::into_raw(Box::new(value)));
+                (*self.root).left = TreePtr::Val(try_box(&self.alloc, value)?);
                 (*self.root).key = MaybeUninit::new(key);
-                return None;
+                self.length += 1;
+                return Ok(None);
             }
 
             let mut tmp_node = self.root;
@@ -91,18 +130,22 @@ where
             }
 
             if &key == (*tmp_node).key.assume_init_ref() {
-                let mut val_ptr = Box::into_raw(Box::new(value));
+                let mut val_ptr = try_box(&self.alloc, value)?;
                 mem::swap(&mut val_ptr, (*tmp_node).left.as_val_mut());
-                return Some(*Box::from_raw(val_ptr));
+                return Ok(Some(unbox(&self.alloc, val_ptr)));
             }
 
+            // Allocate upfront so that a failure leaves the tree untouched
+            self.allocator.try_reserve(2)?;
+            let val_ptr = try_box(&self.alloc, value)?;
+
             if (*tmp_node).key.assume_init_ref() < &key {
                 let old_leaf = self.allocator.get_node();
                 (*old_leaf).left = (*tmp_node).left;
                 (*old_leaf).key = MaybeUninit::new((*tmp_node).key.assume_init_read());
 
                 let new_leaf = self.allocator.get_node();
-                (*new_leaf).left = TreePtr::Val(Box::into_raw(Box::new(value)));
+                (*new_leaf).left = TreePtr::Val(val_ptr);
                 (*new_leaf).key = MaybeUninit::new(key.clone());
 
                 (*tmp_node).left = TreePtr::Node(old_leaf);
@@ -114,13 +157,14 @@ where
                 (*old_leaf).key = MaybeUninit::new((*tmp_node).key.assume_init_read().clone());
 
                 let new_leaf = self.allocator.get_node();
-                (*new_leaf).left = TreePtr::Val(Box::into_raw(Box::new(value)));
+                (*new_leaf).left = TreePtr::Val(val_ptr);
                 (*new_leaf).key = MaybeUninit::new(key);
 
                 (*tmp_node).left = TreePtr::Node(new_leaf);
                 (*tmp_node).right = old_leaf;
             }
-            None
+            self.length += 1;
+            Ok(None)
         }
     }
 
@@ -135,7 +179,7 @@ where
                     (*self.root).key.assume_init_drop();
                     let val_ptr = mem::take(&mut (*self.root).left).as_val();
                     self.length -= 1;
-                    return Some(*Box::from_raw(val_ptr));
+                    return Some(unbox(&self.alloc, val_ptr));
                 } else {
                     return None;
                 }
@@ -168,50 +212,34 @@ where
             self.allocator.return_node(tmp_node);
             self.allocator.return_node(other_node);
             self.length -= 1;
-            Some(*Box::from_raw(val_ptr))
+            Some(unbox(&self.alloc, val_ptr))
         }
     }
 
-    pub fn find<Q>(&self, range: Range<Q>) -> SearchTreeFind<'_, K, V, Q>
-    where
-        Q: Borrow<K>,
-    {
-        let mut iter_stack = LinkedListStack::default();
-        let mut rev_stack = LinkedListStack::default();
-        iter_stack.push(self.root);
-        rev_stack.push(self.root);
-        SearchTreeFind {
-            _tree: self,
-            iter_stack,
-            rev_stack,
-            last_iter_key: None,
-            last_rev_key: None,
-            range,
-        }
+    /// Releases the node blocks left entirely unused by removals.
+    pub fn shrink_to_fit(&mut self) {
+        self.allocator.shrink_to_fit();
     }
 
-    pub fn iter(&self) -> SearchTreeIter<'_, K, V> {
-        let mut iter_stack = LinkedListStack::default();
-        let mut rev_stack = LinkedListStack::default();
-        if unsafe { !(*self.root).is_empty() } {
-            iter_stack.push(self.root);
-            rev_stack.push(self.root);
-        }
-        SearchTreeIter {
-            _tree: self,
-            iter_stack,
-            rev_stack,
-            last_iter_key: None,
-            last_rev_key: None,
-        }
+    pub fn allocator_stats(&self) -> AllocatorStats {
+        self.allocator.stats()
     }
+}
 
-    /// Top-down contruction of an optimal [`SearchTree`]().
-    ///
-    /// # Panics
-    ///
-    /// Panics if `iter` is not sorted (by `K`) or if it contains duplicates.
-    pub fn from_sorted<I>(iter: I) -> Self
+impl<K, V, A> SearchTree<K, V, A>
+where
+    K: Ord + Clone,
+    A: Allocator + Clone,
+{
+    pub fn new_in(block_size: usize, blocks_cap: usize, alloc: A) -> Self {
+        Self::with_allocator(
+            BlockAllocator::new_in(block_size, blocks_cap, alloc.clone()),
+            alloc,
+        )
+    }
+
+    /// Same as [`from_sorted`](SearchTree::from_sorted) but allocating with `alloc`.
+    pub fn from_sorted_in<I>(iter: I, alloc: A) -> Self
     where
         I: IntoIterator<Item = (K, V)>,
         I::IntoIter: ExactSizeIterator,
@@ -237,11 +265,27 @@ where
         let mut iter = iter.into_iter();
         let length = iter.len();
 
-        let mut allocator: BlockAllocator<TreeNode<K, V>> = BlockAllocator::default();
-        let mut stack = BoundedStack::new(length.ilog2() as usize + 1);
+        let mut allocator = BlockAllocator::new_in(
+            BlockAllocator::<TreeNode<K, V>, A>::DEFAULT_BLOCK_SIZE,
+            BlockAllocator::<TreeNode<K, V>, A>::DEFAULT_BLOCK_CAP,
+            alloc.clone(),
+        );
+        if length == 0 {
+            return Self::with_allocator(allocator, alloc);
+        }
+        // At most ilog2(length) + 1 nodes are waiting to be expanded
+        let mut stack: ArrayStack<_, { usize::BITS as usize }> = ArrayStack::new();
+
+        // All the nodes are taken at once, chained through their right pointer
+        let (mut chain, _) = allocator.get_chain(2 * length - 1);
+        let mut next_node = || {
+            let node = chain;
+            chain = unsafe { (*node).right };
+            node
+        };
 
         // Put root node on stack
-        let root = allocator.get_node();
+        let root = next_node();
         current.node1 = root;
         current.number = length; // root expands to length leaves
         stack.push(current);
@@ -255,10 +299,10 @@ where
             if current.number > 1
             // Create (empty) tree nodes
             {
-                left.node1 = allocator.get_node();
+                left.node1 = next_node();
                 left.node2 = current.node2;
                 left.number = current.number / 2;
-                right.node1 = allocator.get_node();
+                right.node1 = next_node();
                 right.node2 = current.node1;
                 right.number = current.number - left.number;
                 unsafe { (*current.node1).left = TreePtr::Node(left.node1) };
@@ -269,7 +313,8 @@ where
             // Reached a leaf, must be filled with list item
             else {
                 let (key, value) = iter.next().unwrap();
-                let val_ptr = TreePtr::Val(Box::into_raw(Box::new(value)));
+                let val_ptr =
+                    TreePtr::Val(try_box(&alloc, value).unwrap_or_else(|err| err.handle()));
                 if !current.node2.is_null() {
                     unsafe { (*current.node2).key = MaybeUninit::new(key.clone()) };
                 }
@@ -291,6 +336,7 @@ where
 
         let tree = Self {
             allocator,
+            alloc,
             root,
             length,
         };
@@ -302,25 +348,95 @@ where
     }
 }
 
-impl<K, V> Drop for SearchTree<K, V> {
+impl<K, V, A, P> SearchTree<K, V, A, P>
+where
+    K: Ord + Clone,
+    A: Allocator + Clone,
+    P: NodePool<TreeNode<K, V>>,
+{
+    pub fn find<Q>(&self, range: Range<Q>) -> SearchTreeFind<'_, K, V, Q, A, P>
+    where
+        Q: Borrow<K>,
+    {
+        // Stacks are dropped in reverse allocation order, so a bump allocator gets it all back
+        let mut rev_stack = self.traversal_stack();
+        rev_stack.push(self.root);
+        let mut iter_stack = self.traversal_stack();
+        iter_stack.push(self.root);
+        SearchTreeFind {
+            _tree: self,
+            iter_stack,
+            rev_stack,
+            last_iter_key: None,
+            last_rev_key: None,
+            range,
+        }
+    }
+
+    pub fn iter(&self) -> SearchTreeIter<'_, K, V, A, P> {
+        // Stacks are dropped in reverse allocation order, so a bump allocator gets it all back
+        let mut rev_stack = self.traversal_stack();
+        if unsafe { !(*self.root).is_empty() } {
+            rev_stack.push(self.root);
+        }
+        let mut iter_stack = self.traversal_stack();
+        if unsafe { !(*self.root).is_empty() } {
+            iter_stack.push(self.root);
+        }
+        SearchTreeIter {
+            _tree: self,
+            iter_stack,
+            rev_stack,
+            last_iter_key: None,
+            last_rev_key: None,
+        }
+    }
+
+    fn traversal_stack(&self) -> TraversalStack<K, V, A> {
+        LinkedListStack::new_in(
+            BlockAllocator::<TreeNode<K, V>, A>::DEFAULT_BLOCK_SIZE,
+            BlockAllocator::<TreeNode<K, V>, A>::DEFAULT_BLOCK_CAP,
+            self.alloc.clone(),
+        )
+    }
+}
+
+impl<K, V, A, P> Drop for SearchTree<K, V, A, P>
+where
+    A: Allocator,
+    P: NodePool<TreeNode<K, V>>,
+{
     fn drop(&mut self) {
         unsafe {
             if (*self.root).is_empty() {
                 self.allocator.return_node(self.root);
                 return;
             }
+            // Freed nodes are chained through their right pointer, then returned at once
+            let mut chain: *mut TreeNode<K, V> = ptr::null_mut();
+            let mut tail: *mut TreeNode<K, V> = ptr::null_mut();
+            let mut chain_len = 0;
+            let mut release = |node: *mut TreeNode<K, V>| {
+                (*node).right = chain;
+                if tail.is_null() {
+                    tail = node;
+                }
+                chain = node;
+                chain_len += 1;
+            };
+
             let mut current_node = self.root;
             while (*current_node).has_subtrees() {
                 if (*(*current_node).left.as_node()).is_leaf() {
                     let leaf_node = (*current_node).left.as_node();
                     let val_ptr = (*leaf_node).left.as_val();
-                    drop(*Box::from_raw(val_ptr));
+                    drop(unbox(&self.alloc, val_ptr));
                     (*leaf_node).key.assume_init_drop();
-                    self.allocator.return_node(leaf_node);
+                    release(leaf_node);
 
                     let tmp = (*current_node).right;
                     (*current_node).key.assume_init_drop();
-                    self.allocator.return_node(current_node);
+                    release(current_node);
                     current_node = tmp;
                 } else {
                     let tmp = (*current_node).left.as_node();
@@ -330,13 +446,15 @@ impl<K, V> Drop for SearchTree<K, V> {
                 }
             }
             let val_ptr = (*current_node).left.as_val();
-            drop(*Box::from_raw(val_ptr));
+            drop(unbox(&self.alloc, val_ptr));
             (*current_node).key.assume_init_drop();
-            self.allocator.return_node(current_node);
+            release(current_node);
+            self.allocator.return_chain(chain, tail, chain_len);
         }
     }
 }
 
+#[cfg(feature = "alloc")]
 impl<K, V> FromIterator<(K, V)> for SearchTree<K, V>
 where
     K: Ord + Clone,
@@ -350,17 +468,23 @@ where
     }
 }
 
-pub struct SearchTreeIter<'a, K, V> {
-    _tree: &'a SearchTree<K, V>,
-    iter_stack: LinkedListStack<*mut TreeNode<K, V>>,
-    rev_stack: LinkedListStack<*mut TreeNode<K, V>>,
+pub struct SearchTreeIter<'a, K, V, A = Global, P = BlockAllocator<TreeNode<K, V>, A>>
+where
+    A: Allocator,
+    P: NodePool<TreeNode<K, V>>,
+{
+    _tree: &'a SearchTree<K, V, A, P>,
+    iter_stack: TraversalStack<K, V, A>,
+    rev_stack: TraversalStack<K, V, A>,
     last_iter_key: Option<&'a K>,
     last_rev_key: Option<&'a K>,
 }
 
-impl<'a, K, V> Iterator for SearchTreeIter<'a, K, V>
+impl<'a, K, V, A, P> Iterator for SearchTreeIter<'a, K, V, A, P>
 where
     K: Ord,
+    A: Allocator,
+    P: NodePool<TreeNode<K, V>>,
 {
     type Item = (&'a K, &'a V);
 
@@ -389,9 +513,11 @@ where
     }
 }
 
-impl<'a, K, V> DoubleEndedIterator for SearchTreeIter<'a, K, V>
+impl<'a, K, V, A, P> DoubleEndedIterator for SearchTreeIter<'a, K, V, A, P>
 where
     K: Ord,
+    A: Allocator,
+    P: NodePool<TreeNode<K, V>>,
 {
     fn next_back(&mut self) -> Option<Self::Item> {
         while !self.rev_stack.is_empty() {
@@ -418,21 +544,33 @@ where
     }
 }
 
-impl<'a, K, V> FusedIterator for SearchTreeIter<'a, K, V> where K: Ord {}
+impl<'a, K, V, A, P> FusedIterator for SearchTreeIter<'a, K, V, A, P>
+where
+    K: Ord,
+    A: Allocator,
+    P: NodePool<TreeNode<K, V>>,
+{
+}
 
-pub struct SearchTreeFind<'a, K, V, Q> {
-    _tree: &'a SearchTree<K, V>,
-    iter_stack: LinkedListStack<*mut TreeNode<K, V>>,
-    rev_stack: LinkedListStack<*mut TreeNode<K, V>>,
+pub struct SearchTreeFind<'a, K, V, Q, A = Global, P = BlockAllocator<TreeNode<K, V>, A>>
+where
+    A: Allocator,
+    P: NodePool<TreeNode<K, V>>,
+{
+    _tree: &'a SearchTree<K, V, A, P>,
+    iter_stack: TraversalStack<K, V, A>,
+    rev_stack: TraversalStack<K, V, A>,
     last_iter_key: Option<&'a K>,
     last_rev_key: Option<&'a K>,
     range: Range<Q>,
 }
 
-impl<'a, K, V, Q> Iterator for SearchTreeFind<'a, K, V, Q>
+impl<'a, K, V, Q, A, P> Iterator for SearchTreeFind<'a, K, V, Q, A, P>
 where
     Q: Borrow<K>,
     K: Ord,
+    A: Allocator,
+    P: NodePool<TreeNode<K, V>>,
 {
     type Item = (&'a K, &'a V);
 
@@ -467,10 +605,12 @@ where
     }
 }
 
-impl<'a, K, V, Q> DoubleEndedIterator for SearchTreeFind<'a, K, V, Q>
+impl<'a, K, V, Q, A, P> DoubleEndedIterator for SearchTreeFind<'a, K, V, Q, A, P>
 where
     Q: Borrow<K>,
     K: Ord,
+    A: Allocator,
+    P: NodePool<TreeNode<K, V>>,
 {
     fn next_back(&mut self) -> Option<Self::Item> {
         while !self.rev_stack.is_empty() {
@@ -503,19 +643,23 @@ where
     }
 }
 
-impl<'a, K, V, Q> FusedIterator for SearchTreeFind<'a, K, V, Q>
+impl<'a, K, V, Q, A, P> FusedIterator for SearchTreeFind<'a, K, V, Q, A, P>
 where
     Q: Borrow<K>,
     K: Ord,
+    A: Allocator,
+    P: NodePool<TreeNode<K, V>>,
 {
 }
 
-impl<K, V> IntoIterator for SearchTree<K, V>
+impl<K, V, A, P> IntoIterator for SearchTree<K, V, A, P>
 where
     K: Ord,
+    A: Allocator,
+    P: NodePool<TreeNode<K, V>>,
 {
     type Item = (K, V);
-    type IntoIter = SearchTreeIntoIter<K, V>;
+    type IntoIter = SearchTreeIntoIter<K, V, A, P>;
 
     fn into_iter(self) -> Self::IntoIter {
         let tree = ManuallyDrop::new(self);
@@ -526,17 +670,21 @@ where
     }
 }
 
-pub struct SearchTreeIntoIter<K, V>
+pub struct SearchTreeIntoIter<K, V, A = Global, P = BlockAllocator<TreeNode<K, V>, A>>
 where
     K: Ord,
+    A: Allocator,
+    P: NodePool<TreeNode<K, V>>,
 {
     current_node: *mut TreeNode<K, V>,
-    tree: ManuallyDrop<SearchTree<K, V>>,
+    tree: ManuallyDrop<SearchTree<K, V, A, P>>,
 }
 
-impl<K, V> Iterator for SearchTreeIntoIter<K, V>
+impl<K, V, A, P> Iterator for SearchTreeIntoIter<K, V, A, P>
 where
     K: Ord,
+    A: Allocator,
+    P: NodePool<TreeNode<K, V>>,
 {
     type Item = (K, V);
 
@@ -549,7 +697,7 @@ where
                 if (*(*self.current_node).left.as_node()).is_leaf() {
                     let leaf_node = (*self.current_node).left.as_node();
                     let val_ptr = (*leaf_node).left.as_val();
-                    let val = *Box::from_raw(val_ptr);
+                    let val = unbox(&self.tree.alloc, val_ptr);
                     let key = (*leaf_node).key.assume_init_read();
                     self.tree.allocator.return_node(leaf_node);
 
@@ -567,7 +715,7 @@ where
                 }
             }
             let val_ptr = (*self.current_node).left.as_val();
-            let val = *Box::from_raw(val_ptr);
+            let val = unbox(&self.tree.alloc, val_ptr);
             let key = (*self.current_node).key.assume_init_read();
             self.tree.allocator.return_node(self.current_node);
             self.current_node = ptr::null_mut();
@@ -576,18 +724,50 @@ where
     }
 }
 
-impl<K, V> Drop for SearchTreeIntoIter<K, V>
+impl<K, V, A, P> Drop for SearchTreeIntoIter<K, V, A, P>
 where
     K: Ord,
+    A: Allocator,
+    P: NodePool<TreeNode<K, V>>,
 {
     fn drop(&mut self) {
         unsafe {
             while self.next().is_some() {}
             ptr::drop_in_place(&mut self.tree.allocator as *mut _);
+            ptr::drop_in_place(&mut self.tree.alloc as *mut _);
         }
     }
 }
 
+type TraversalStack<K, V, A> =
+    LinkedListStack<*mut TreeNode<K, V>, BlockAllocator<Node<*mut TreeNode<K, V>>, A>>;
+
+/// Moves `value` to memory allocated with `alloc`, like `Box::into_raw(Box::new_in(..))`.
+pub(super) fn try_box<V, A>(alloc: &A, value: V) -> Result<*mut V, AllocError>
+where
+    A: Allocator,
+{
+    let ptr = alloc.allocate(Layout::new::<V>())?.as_ptr() as *mut V;
+    unsafe { ptr.write(value) };
+    Ok(ptr)
+}
+
+/// Moves back a value allocated by [`try_box`].
+///
+/// # Safety
+///
+/// `ptr` must come from [`try_box`] with the same `alloc`.
+pub(super) unsafe fn unbox<V, A>(alloc: &A, ptr: *mut V) -> V
+where
+    A: Allocator,
+{
+    unsafe {
+        let value = ptr.read();
+        alloc.deallocate(NonNull::new_unchecked(ptr as *mut u8), Layout::new::<V>());
+        value
+    }
+}
+
 ////////////////////////////////////////////////////////////////////////////////////////
 
 #[derive(Debug)]
@@ -723,6 +903,7 @@ mod tests {
     use super::*;
 
     #[test]
+    #[cfg(feature = "alloc")]
     fn search_tree_ok() {
         let mut tree = SearchTree::default();
         tree.insert(5, 50);
@@ -749,12 +930,85 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "alloc")]
+    fn search_tree_try_insert() {
+        let mut tree = SearchTree::new(2, 1);
+        assert_eq!(Ok(None), tree.try_insert(1, 10));
+        assert_eq!(Ok(None), tree.try_insert(2, 20));
+        assert_eq!(Ok(Some(20)), tree.try_insert(2, 21));
+        assert_eq!(2, tree.len());
+        assert_eq!(Some(&21), tree.get(&2));
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn search_tree_shrink_to_fit() {
+        let mut tree = SearchTree::new(4, 1);
+        for i in 0..100 {
+            tree.insert(i, i);
+        }
+        for i in 0..99 {
+            assert_eq!(Some(i), tree.remove(&i));
+        }
+        tree.shrink_to_fit();
+        assert_eq!(Some(&99), tree.get(&99));
+        for i in 0..100 {
+            tree.insert(i, i);
+        }
+        assert_eq!(100, tree.len());
+        assert_eq!((0..100).sum::<i32>(), tree.iter().map(|(_, v)| v).sum());
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn search_tree_shared_pool() {
+        use crate::allocator::SharedPool;
+        let pool = SharedPool::new(32, 1);
+        let mut even = SearchTree::with_allocator(pool.clone(), Global);
+        let mut odd = SearchTree::with_allocator(pool.clone(), Global);
+        for i in 0..8 {
+            even.insert(2 * i, i);
+            odd.insert(2 * i + 1, i);
+        }
+        assert_eq!(1, even.allocator_stats().blocks);
+        assert!(odd.find(0..6).map(|(k, _)| *k).eq([1, 3, 5]));
+        drop(even);
+        assert_eq!(15, pool.stats().live_nodes);
+        assert_eq!(8, odd.into_iter().count());
+        assert_eq!(0, pool.stats().live_nodes);
+    }
+
+    #[test]
+    fn search_tree_region() {
+        use crate::allocator::Region;
+        static mut REGION: [MaybeUninit<u8>; 65536] = [MaybeUninit::uninit(); 65536];
+        let region = Region::new(unsafe { &mut *ptr::addr_of_mut!(REGION) });
+        let mut tree = SearchTree::new_in(8, 2, &region);
+        for i in 0..10 {
+            assert_eq!(Ok(None), tree.try_insert(i, i * 10));
+        }
+        assert_eq!(Some(&50), tree.get(&5));
+        assert_eq!(Some(50), tree.remove(&5));
+        let remaining = region.remaining();
+        assert_eq!(9, tree.iter().count());
+        assert_eq!(3, tree.find(2..6).count());
+        assert_eq!(remaining, region.remaining());
+        drop(tree);
+
+        let tree = SearchTree::from_sorted_in([(1, 10), (2, 20), (3, 30)], &region);
+        assert_eq!(Some(&20), tree.get(&2));
+        assert!(SearchTree::<usize, usize, _>::from_sorted_in([], &region).is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
     #[should_panic(expected = "iterator keys are not sorted or unique")]
     fn search_tree_unsorted() {
         let _tree = SearchTree::from_sorted([(3, 30), (1, 10), (4, 40), (2, 20)]);
     }
 
     #[test]
+    #[cfg(feature = "alloc")]
     fn search_tree_iter() {
         let tree = SearchTree::from_sorted([(1, 10), (2, 20), (3, 30), (4, 40)]);
 
@@ -796,6 +1050,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "alloc")]
     fn search_tree_find() {
         let tree = SearchTree::from_sorted([(1, 10), (2, 20), (3, 30), (4, 40)]);
         for ((&k, &v), i) in tree.find(2..5).zip(2..5) {